@@ -0,0 +1,186 @@
+use crate::types::engine::{Rounding, Settings};
+use crate::types::error::RunErr;
+use crate::types::number::Number;
+use crate::types::random::Rng;
+use crate::types::value::List;
+
+/// How many arguments a function takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(expected) => count == *expected,
+            Arity::AtLeast(minimum) => count >= *minimum,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Arity::Exactly(1) => String::from("1 argument"),
+            Arity::Exactly(expected) => format!("{expected} arguments"),
+            Arity::AtLeast(1) => String::from("at least 1 argument"),
+            Arity::AtLeast(minimum) => format!("at least {minimum} arguments"),
+        }
+    }
+}
+
+/// The result of a function is `None` when it overflows, the same as with the operations on numbers.
+/// Any other error is returned as is, and errors which need the expression (like `DivisionByZero`)
+/// have it filled in by the evaluator.
+type Function = fn(&[Number], &Settings) -> Result<Option<Number>, RunErr>;
+
+/// Functions which look into a list, given as the first argument, with the names of the items after it.
+type ListFunction = fn(&List, &[String]) -> Number;
+
+/// Functions which roll, and so need the generator. A range which isn't valid is returned without the
+/// expression, like a division by zero.
+type RandomFunction = fn(&[Number], &Rng) -> Result<Option<Number>, RunErr>;
+
+/// What the function works on, which decides how its arguments are evaluated.
+#[derive(Clone, Copy)]
+pub enum Body {
+    Numbers(Function),
+    Random(RandomFunction),
+    Items(ListFunction),
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub body: Body,
+}
+
+/// All of the functions which can be called in expressions.
+///
+/// The arity is checked before the function is called, so the functions can simply index the arguments.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "min",
+        arity: Arity::AtLeast(1),
+        body: Body::Numbers(|args, _| Ok(args.iter().min().cloned())),
+    },
+    Builtin {
+        name: "max",
+        arity: Arity::AtLeast(1),
+        body: Body::Numbers(|args, _| Ok(args.iter().max().cloned())),
+    },
+    // `clamp(hp, 0, max_hp)` keeps the value between the bounds. Bounds the wrong way around are most likely
+    // a mistake in the text, so they're an error rather than quietly picking one of them.
+    Builtin {
+        name: "clamp",
+        arity: Arity::Exactly(3),
+        body: Body::Numbers(|args, _| {
+            if args[1] > args[2] {
+                return Err(RunErr::InvalidBounds(String::new()));
+            }
+            Ok(Some(
+                args[0].clone().min(args[2].clone()).max(args[1].clone()),
+            ))
+        }),
+    },
+    Builtin {
+        name: "abs",
+        arity: Arity::Exactly(1),
+        body: Body::Numbers(|args, _| {
+            Ok(if args[0].isNegative() {
+                args[0].neg()
+            } else {
+                Some(args[0].clone())
+            })
+        }),
+    },
+    // -1, 0 or 1, depending on the sign of the number.
+    Builtin {
+        name: "sign",
+        arity: Arity::Exactly(1),
+        body: Body::Numbers(|args, _| {
+            Ok(Some(Number::from(if args[0].isNegative() {
+                -1
+            } else {
+                i64::from(!args[0].isZero())
+            })))
+        }),
+    },
+    Builtin {
+        name: "pow",
+        arity: Arity::Exactly(2),
+        body: Body::Numbers(|args, settings| super::power(&args[0], &args[1], settings)),
+    },
+    // Division which always rounds down, no matter how the author set up the rounding.
+    Builtin {
+        name: "floor_div",
+        arity: Arity::Exactly(2),
+        body: Body::Numbers(|args, settings| {
+            if args[1].isZero() {
+                return Err(RunErr::DivisionByZero(String::new()));
+            }
+
+            let floored = Settings {
+                rounding: Rounding::Floor,
+                ..*settings
+            };
+            Ok(args[0].div(&args[1], &floored))
+        }),
+    },
+    // `random(1, 100)` is any whole number between the two, including them, and goes into the log of rolls.
+    Builtin {
+        name: "random",
+        arity: Arity::Exactly(2),
+        body: Body::Random(|args, rng| {
+            let (Some(low), Some(high)) = (args[0].toWhole(), args[1].toWhole()) else {
+                return Err(RunErr::InvalidRange(String::new()));
+            };
+            if low > high {
+                return Err(RunErr::InvalidRange(String::new()));
+            }
+
+            let result = Number::from(rng.between(low, high));
+            rng.record(format!("random({low}, {high})"), vec![result.clone()]);
+            Ok(Some(result))
+        }),
+    },
+    // `count(inventory, "Health Potion")` is how many of the item there are, and `count(inventory)` how many
+    // items there are in total.
+    Builtin {
+        name: "count",
+        arity: Arity::AtLeast(1),
+        body: Body::Items(|list, items| match items {
+            [] => Number::from(list.count(None)),
+            items => Number::from(items.iter().fold(0, |total: i64, item| {
+                total.saturating_add(list.count(Some(item)))
+            })),
+        }),
+    },
+    // Whether there's at least one of every item given.
+    Builtin {
+        name: "contains",
+        arity: Arity::AtLeast(2),
+        body: Body::Items(|list, items| {
+            Number::from(items.iter().all(|item| list.count(Some(item)) > 0))
+        }),
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Builtin {
+    /// The arguments have to be checked before the function is called, and before they're evaluated,
+    /// as the evaluator needs to know where the list is.
+    pub fn checkArity(&self, count: usize) -> Result<(), RunErr> {
+        if self.arity.accepts(count) {
+            Ok(())
+        } else {
+            Err(RunErr::WrongArity(
+                self.name.to_string(),
+                self.arity.describe(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use crate::types::curve::{Curve, Thresholds};
+use crate::types::error::RunErr;
+use crate::types::{Command, Content, Expression};
+
+/// The arguments of a macro call, by the name of the parameter they're given for.
+pub type Arguments<'a> = HashMap<&'a str, &'a Expression>;
+
+/// Writes out the contents of a macro with the arguments in place of the parameters.
+///
+/// A parameter used in an expression is replaced by the argument, which is already evaluated by the time the
+/// macro is expanded, unless it's a name. Anywhere a name is expected, like the variable of `add` or the entity in
+/// `who.level`, the argument has to be a name itself, so `levelup(hero)` turns `add who.level 1` into
+/// `add hero.level 1`.
+pub fn expand(contents: &[Content], arguments: &Arguments) -> Result<Vec<Content>, RunErr> {
+    contents
+        .iter()
+        .map(|content| expandContent(content, arguments))
+        .collect()
+}
+
+fn expandContent(content: &Content, arguments: &Arguments) -> Result<Content, RunErr> {
+    let expression = |expr: &Expression| substitute(expr, arguments);
+    let index = |index: &Option<String>| {
+        index
+            .as_ref()
+            .map(|name| rename(name, arguments))
+            .transpose()
+    };
+
+    Ok(match content {
+        Content::Text(text) => Content::Text(text.to_owned()),
+        Content::Command(command) => Content::Command(expandCommand(command, arguments)?),
+        Content::Conditional(branches, otherwise) => Content::Conditional(
+            branches
+                .iter()
+                .map(|(condition, contents)| {
+                    Ok((expression(condition)?, expand(contents, arguments)?))
+                })
+                .collect::<Result<Vec<(Expression, Vec<Content>)>, RunErr>>()?,
+            expand(otherwise, arguments)?,
+        ),
+        Content::Repeat(times, name, contents) => Content::Repeat(
+            expression(times)?,
+            index(name)?,
+            expand(contents, arguments)?,
+        ),
+        Content::While(condition, name, contents) => Content::While(
+            expression(condition)?,
+            index(name)?,
+            expand(contents, arguments)?,
+        ),
+        // The parameters of a macro defined inside of another one hide the ones of the outer macro.
+        Content::Macro(name, parameters, contents) => {
+            let mut inner = arguments.clone();
+            inner.retain(|parameter, _| !parameters.iter().any(|hidden| hidden == parameter));
+
+            Content::Macro(
+                name.to_owned(),
+                parameters.to_vec(),
+                expand(contents, &inner)?,
+            )
+        }
+    })
+}
+
+fn expandCommand(command: &Command, arguments: &Arguments) -> Result<Command, RunErr> {
+    let expression = |expr: &Expression| substitute(expr, arguments);
+    let name = |name: &String| rename(name, arguments);
+    let label = |label: &String| relabel(label, arguments);
+    let fields = |fields: &[(String, Expression)]| {
+        fields
+            .iter()
+            .map(|(field, formula)| Ok((field.to_owned(), expression(formula)?)))
+            .collect::<Result<Vec<(String, Expression)>, RunErr>>()
+    };
+
+    Ok(match command {
+        Command::Let(var, expr) => Command::Let(name(var)?, expression(expr)?),
+        Command::Set(var, expr) => Command::Set(name(var)?, expression(expr)?),
+        Command::Add(var, expr) => Command::Add(name(var)?, expression(expr)?),
+        Command::Subtract(var, expr) => Command::Subtract(name(var)?, expression(expr)?),
+        Command::Derive(var, expr) => Command::Derive(name(var)?, expression(expr)?),
+        Command::Config(setting) => Command::Config(*setting),
+        Command::Write(expr, format) => Command::Write(expression(expr)?, *format),
+        Command::Entity(entity) => Command::Entity(name(entity)?),
+        Command::Fields(entity) => Command::Fields(name(entity)?),
+        Command::Delete(entity) => Command::Delete(name(entity)?),
+        Command::Class(class, formulas) => Command::Class(class.to_owned(), fields(formulas)?),
+        Command::New(entity, class, overrides) => {
+            Command::New(name(entity)?, class.to_owned(), fields(overrides)?)
+        }
+        Command::List(list) => Command::List(name(list)?),
+        Command::Push(list, item, quantity) => {
+            Command::Push(name(list)?, expression(item)?, expression(quantity)?)
+        }
+        Command::Remove(list, item, quantity) => {
+            Command::Remove(name(list)?, expression(item)?, expression(quantity)?)
+        }
+        Command::Pool(pool, max) => Command::Pool(name(pool)?, expression(max)?),
+        Command::Effect(effect, target, amount, duration, unit) => Command::Effect(
+            label(effect)?,
+            name(target)?,
+            expression(amount)?,
+            expression(duration)?,
+            *unit,
+        ),
+        Command::Tick(ticks) => Command::Tick(expression(ticks)?),
+        Command::Chapter => Command::Chapter,
+        Command::Effects(target) => Command::Effects(target.as_ref().map(name).transpose()?),
+        Command::Curve(experience, curve) => Command::Curve(
+            name(experience)?,
+            Curve {
+                level: name(&curve.level)?,
+                thresholds: match &curve.thresholds {
+                    Thresholds::Formula(formula) => Thresholds::Formula(expression(formula)?),
+                    Thresholds::Table(table) => {
+                        Thresholds::Table(table.iter().map(expression).collect::<Result<
+                            Vec<Expression>,
+                            RunErr,
+                        >>(
+                        )?)
+                    }
+                },
+                hook: match &curve.hook {
+                    Some((called, args)) => Some((
+                        called.to_owned(),
+                        args.iter()
+                            .map(expression)
+                            .collect::<Result<Vec<Expression>, RunErr>>()?,
+                    )),
+                    None => None,
+                },
+            },
+        ),
+        Command::Modifier(stat, source, amount, percent) => {
+            Command::Modifier(name(stat)?, label(source)?, expression(amount)?, *percent)
+        }
+        Command::Revoke(source) => Command::Revoke(label(source)?),
+        Command::Explain(stat) => Command::Explain(name(stat)?),
+        Command::Item(item, slot, bonuses) => {
+            Command::Item(item.to_owned(), slot.to_owned(), fields(bonuses)?)
+        }
+        Command::Equip(entity, item) => Command::Equip(name(entity)?, label(item)?),
+        Command::Unequip(entity, slot) => Command::Unequip(name(entity)?, label(slot)?),
+        Command::Points(pool, amount) => Command::Points(name(pool)?, expression(amount)?),
+        Command::Allocate(stat, pool, amount) => {
+            Command::Allocate(name(stat)?, name(pool)?, expression(amount)?)
+        }
+        Command::Skill(skill, max, prerequisites) => Command::Skill(
+            skill.to_owned(),
+            expression(max)?,
+            prerequisites
+                .iter()
+                .map(expression)
+                .collect::<Result<Vec<Expression>, RunErr>>()?,
+        ),
+        Command::Learn(entity, skill) => Command::Learn(name(entity)?, label(skill)?),
+        Command::Rankup(entity, skill) => Command::Rankup(name(entity)?, label(skill)?),
+        Command::Skills(entity) => Command::Skills(name(entity)?),
+        Command::Seed(seed) => Command::Seed(*seed),
+        Command::Roll(expr) => Command::Roll(expression(expr)?),
+        Command::Call(called, args) => Command::Call(
+            called.to_owned(),
+            args.iter()
+                .map(expression)
+                .collect::<Result<Vec<Expression>, RunErr>>()?,
+        ),
+        // The markers are folded into blocks while parsing, but they're still written out as they are.
+        Command::If(condition) => Command::If(expression(condition)?),
+        Command::ElseIf(condition) => Command::ElseIf(expression(condition)?),
+        Command::Else => Command::Else,
+        Command::Repeat(times, index) => Command::Repeat(expression(times)?, index.clone()),
+        Command::While(condition, index) => Command::While(expression(condition)?, index.clone()),
+        Command::Def(called, parameters) => Command::Def(called.to_owned(), parameters.to_vec()),
+        Command::End => Command::End,
+    })
+}
+
+fn substitute(expression: &Expression, arguments: &Arguments) -> Result<Expression, RunErr> {
+    expression.replaceVariables(&|variable| match arguments.get(variable) {
+        Some(argument) => Ok((*argument).clone()),
+        None => rename(variable, arguments).map(Expression::Variable),
+    })
+}
+
+/// Replaces the parameter at the start of a name (`who` in `who.level`) with the name given as the argument.
+fn rename(name: &str, arguments: &Arguments) -> Result<String, RunErr> {
+    let (first, rest) = match name.split_once('.') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (name, None),
+    };
+
+    match arguments.get(first) {
+        None => Ok(name.to_string()),
+        Some(Expression::Variable(argument)) => Ok(match rest {
+            Some(rest) => format!("{argument}.{rest}"),
+            None => argument.to_owned(),
+        }),
+        Some(argument) => Err(RunErr::NotAVariable(argument.to_string())),
+    }
+}
+
+/// Replaces a parameter standing for the name of an item, a skill, a slot, an effect or the source of a modifier.
+/// Those names aren't variables, so the argument can be a string as well, like `gear(hero, "Iron Sword")`.
+fn relabel(label: &str, arguments: &Arguments) -> Result<String, RunErr> {
+    match arguments.get(label) {
+        None => Ok(label.to_string()),
+        Some(Expression::Variable(argument) | Expression::Str(argument)) => Ok(argument.to_owned()),
+        Some(argument) => Err(RunErr::NotAName(argument.to_string())),
+    }
+}
@@ -1,43 +1,1216 @@
-use crate::types::engine::Storage;
+use crate::types::curve::{Curve, Thresholds};
+use crate::types::effect::{Effect, TimeUnit};
+use crate::types::engine::{NumberMode, Settings, Storage};
 use crate::types::error::RunErr;
-use crate::types::{Command, Content};
+use crate::types::modifier::{Modifier, Origin};
+use crate::types::number::Number;
+use crate::types::value::{describeItem, List, Pool, Value};
+use crate::types::{BiOperation, Command, Content, Expression, Format, UnOperation};
 
+mod builtins;
+mod macros;
+#[cfg(test)]
 mod tests;
 
 pub fn run(storage: &mut Storage, parsed_file: Vec<Content>) -> Result<String, RunErr> {
     // Start interpreting the file
     let mut output: String = String::new();
 
-    for chunk in parsed_file {
-        match chunk {
-            Content::Text(t) => {
-                // If the `Content` is just a chunk of text, simply add it to the output.
-                output += &t;
-                Ok(())
+    for chunk in &parsed_file {
+        runContent(storage, chunk, &mut output)?;
+    }
+
+    Ok(output)
+}
+
+fn runContent(storage: &mut Storage, chunk: &Content, output: &mut String) -> Result<(), RunErr> {
+    match chunk {
+        Content::Text(t) => {
+            // If the `Content` is just a chunk of text, simply add it to the output.
+            *output += t;
+            Ok(())
+        }
+
+        // If the `Content` is a command, execute it.
+        Content::Command(command) => {
+            runCommand(storage, command, output).map_err(|err| match err {
+                // The evaluator doesn't know which command it's computing the value for, so the context
+                // of the overflow is filled in here. An overflow inside of a macro already has it.
+                RunErr::ArithmeticOverflow(subject, context) if context.is_empty() => {
+                    RunErr::ArithmeticOverflow(
+                        command.variable().map_or(subject, String::from),
+                        command.keyword().to_string(),
+                    )
+                }
+                other => other,
+            })
+        }
+
+        // Only the first branch whose condition holds is run, and if none of them do, the `else` is.
+        Content::Conditional(branches, otherwise) => {
+            let mut taken = otherwise;
+            for (condition, contents) in branches {
+                if evaluateFor(storage, condition, "if")?.isTrue() {
+                    taken = contents;
+                    break;
+                }
+            }
+
+            for content in taken {
+                runContent(storage, content, output)?;
+            }
+            Ok(())
+        }
+
+        Content::Repeat(times, index, contents) => {
+            let count = match evaluateFor(storage, times, "repeat")? {
+                number if number.isFractional() => {
+                    return Err(RunErr::InvalidRepeatCount(times.to_string()));
+                }
+                number if number.isNegative() => 0,
+                // A number too big for `u32` is definitely over the limit.
+                number => number
+                    .toWhole()
+                    .and_then(|count| u32::try_from(count).ok())
+                    .unwrap_or(u32::MAX),
+            };
+
+            // The count is known upfront, so there's no need to write out the first few thousand iterations
+            // before failing.
+            let limit = storage.settings().iterations;
+            if count > limit {
+                return Err(RunErr::IterationLimit(limit));
+            }
+
+            runLoop(storage, index, contents, output, |_, iteration| {
+                Ok(iteration <= count)
+            })
+        }
+
+        Content::While(condition, index, contents) => {
+            runLoop(storage, index, contents, output, |storage, _| {
+                Ok(evaluateFor(storage, condition, "while")?.isTrue())
+            })
+        }
+
+        Content::Macro(name, parameters, contents) => {
+            storage.defineMacro(name.to_owned(), parameters.to_vec(), contents.to_vec());
+            Ok(())
+        }
+    }
+}
+
+/// Runs the contents of a loop for as long as `proceed` allows it (it's given the index of the next iteration),
+/// keeping the index variable up to date. The variable only exists while the loop is running, but it's there
+/// from before the first check, so that `while i < 3 as i` works. A name which is already taken is an error,
+/// instead of the loop overwriting the variable and deleting it afterwards.
+fn runLoop(
+    storage: &mut Storage,
+    index: &Option<String>,
+    contents: &[Content],
+    output: &mut String,
+    proceed: impl FnMut(&Storage, u32) -> Result<bool, RunErr>,
+) -> Result<(), RunErr> {
+    if let Some(name) = index {
+        storage.createVariable(name.to_owned(), Value::from(1))?;
+    }
+
+    let result = iterate(storage, index, contents, output, proceed);
+
+    if let Some(name) = index {
+        storage.removeVariable(name);
+    }
+    result
+}
+
+fn iterate(
+    storage: &mut Storage,
+    index: &Option<String>,
+    contents: &[Content],
+    output: &mut String,
+    mut proceed: impl FnMut(&Storage, u32) -> Result<bool, RunErr>,
+) -> Result<(), RunErr> {
+    let limit = storage.settings().iterations;
+    let mut iteration: u32 = 1;
+
+    loop {
+        if let Some(name) = index {
+            let value = Value::from(i64::from(iteration));
+            storage.modifyVariable(name.to_owned(), |_| Ok(value))?;
+        }
+
+        if !proceed(storage, iteration)? {
+            return Ok(());
+        }
+        if iteration > limit {
+            return Err(RunErr::IterationLimit(limit));
+        }
+
+        for content in contents {
+            runContent(storage, content, output)?;
+        }
+
+        iteration += 1;
+    }
+}
+
+/// Evaluates the expression of a block, naming the block in case of an overflow.
+fn evaluateFor(
+    storage: &Storage,
+    expression: &Expression,
+    keyword: &str,
+) -> Result<Number, RunErr> {
+    evaluateNumber(storage, expression).map_err(|err| match err {
+        RunErr::ArithmeticOverflow(subject, _) => {
+            RunErr::ArithmeticOverflow(subject, keyword.to_string())
+        }
+        other => other,
+    })
+}
+
+/// Creates the entity from its class. The overrides replace the formulas of the class, and any fields the class
+/// doesn't have are added after its own.
+///
+/// Inside of the formulas, the names of the fields refer to the fields of the entity, so `str: 10 + level * 2`
+/// becomes `hero.str` derived from `10 + hero.level * 2`. Any other name is an ordinary variable.
+/// A formula which doesn't depend on anything, like `level: 1`, is simply the starting value of the field,
+/// so it can be changed later with `add hero.level 1`.
+fn instantiate(
+    storage: &mut Storage,
+    entity: &str,
+    class: &str,
+    overrides: &[(String, Expression)],
+    output: &mut String,
+) -> Result<(), RunErr> {
+    let mut fields = storage.getClass(class)?.to_vec();
+    for (field, formula) in overrides {
+        match fields.iter_mut().find(|(existing, _)| existing == field) {
+            Some(existing) => existing.1 = formula.clone(),
+            None => fields.push((field.to_owned(), formula.clone())),
+        }
+    }
+
+    storage.createEntity(entity.to_string())?;
+
+    let names: Vec<String> = fields.iter().map(|(field, _)| field.to_owned()).collect();
+    let scope = |variable: &str| {
+        Ok::<Expression, RunErr>(Expression::Variable(
+            if names.iter().any(|name| name == variable) {
+                format!("{entity}.{variable}")
+            } else {
+                variable.to_string()
+            },
+        ))
+    };
+
+    for (field, formula) in &fields {
+        let variable = format!("{entity}.{field}");
+        let formula = formula.replaceVariables(&scope)?;
+
+        let command = if formula.variables().is_empty() {
+            Command::Let(variable, formula)
+        } else {
+            Command::Derive(variable, formula)
+        };
+
+        // The entity is created as a whole or not at all, so a field which fails takes the others with it.
+        if let Err(err) = runCommand(storage, &command, output) {
+            storage.deleteEntity(entity)?;
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+fn runCommand(storage: &mut Storage, command: &Command, output: &mut String) -> Result<(), RunErr> {
+    executeCommand(storage, command, output)?;
+
+    // Gaining experience can be worth a level, or several of them.
+    match command {
+        Command::Let(variable, _) | Command::Set(variable, _) | Command::Add(variable, _) => {
+            levelUp(storage, variable, output)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn executeCommand(
+    storage: &mut Storage,
+    command: &Command,
+    output: &mut String,
+) -> Result<(), RunErr> {
+    // An overflow while modifying a variable is reported with the name of that variable.
+    let overflow =
+        |variable: &String| RunErr::ArithmeticOverflow(variable.to_owned(), String::new());
+    let numbers = storage.settings().numbers;
+
+    // The values are expressions, so they have to be evaluated against the storage first.
+    match command {
+        Command::Let(variable, expression) => {
+            let val = evaluate(storage, expression)?;
+            storage.createVariable(variable.to_owned(), val)
+        }
+        // The maximum of a pool isn't a variable of its own, so changing it is handled separately.
+        Command::Set(variable, expression)
+        | Command::Add(variable, expression)
+        | Command::Subtract(variable, expression)
+            if maximumOf(storage, variable).is_some() =>
+        {
+            changeMaximum(storage, command, variable, expression)
+        }
+        Command::Set(variable, expression) => {
+            let val = evaluate(storage, expression)?;
+            storage.modifyVariable(variable.to_owned(), |og| match (og, val) {
+                // Setting a pool to a number only changes how full it is.
+                (Value::Pool(pool), Value::Number(val)) => Ok(Value::Pool(pool.withCurrent(val))),
+                (Value::Number(_), Value::Pool(pool)) => Ok(Value::Number(pool.current().clone())),
+                // A variable keeps its type, so a number can't become a string, nor a list a number.
+                (og, val) if og.typeName() != val.typeName() => {
+                    Err(mismatch(expression, &val, og.typeName()))
+                }
+                (_, val) => Ok(val),
+            })
+        }
+        Command::Add(variable, expression) => {
+            let val = evaluate(storage, expression)?;
+            storage.modifyVariable(variable.to_owned(), |og| match (og, val) {
+                // Adding to a string variable appends to it, like `add title " the Brave"`.
+                (Value::Str(og), val) => Ok(Value::Str(format!("{og}{val}"))),
+                (og, val) => match (og, val.numeric()) {
+                    (Value::Number(og), Value::Number(val)) => fitting(numbers, og.add(&val))
+                        .map(Value::Number)
+                        .ok_or_else(|| overflow(variable)),
+                    (Value::Pool(pool), Value::Number(val)) => {
+                        fitting(numbers, pool.current().add(&val))
+                            .map(|current| Value::Pool(pool.withCurrent(current)))
+                            .ok_or_else(|| overflow(variable))
+                    }
+                    (Value::Number(_) | Value::Pool(_), val) => {
+                        Err(mismatch(expression, &val, "a number"))
+                    }
+                    (list, _) => Err(mismatch(
+                        &Expression::Variable(variable.to_owned()),
+                        &list,
+                        "a number",
+                    )),
+                },
+            })
+        }
+        Command::Subtract(variable, expression) => {
+            let val = evaluateNumber(storage, expression)?;
+            storage.modifyVariable(variable.to_owned(), |og| match og {
+                Value::Number(og) => fitting(numbers, og.sub(&val))
+                    .map(Value::Number)
+                    .ok_or_else(|| overflow(variable)),
+                Value::Pool(pool) => fitting(numbers, pool.current().sub(&val))
+                    .map(|current| Value::Pool(pool.withCurrent(current)))
+                    .ok_or_else(|| overflow(variable)),
+                text => Err(mismatch(
+                    &Expression::Variable(variable.to_owned()),
+                    &text,
+                    "a number",
+                )),
+            })
+        }
+        Command::Derive(variable, expression) => {
+            // A derived variable is computed again every time it's read, so any dice in it would be rolled
+            // again on every read, and the variable would never hold still.
+            if rolls(expression) {
+                return Err(RunErr::RandomDerived(variable.to_owned()));
+            }
+            storage.createDerived(variable.to_owned(), expression.clone())
+        }
+        Command::Entity(entity) => storage.createEntity(entity.to_owned()),
+        Command::Delete(entity) => storage.deleteEntity(entity),
+        Command::Fields(entity) => {
+            let mut fields: Vec<String> = Vec::new();
+            for field in storage.getFields(entity)? {
+                let value = evaluate(storage, &Expression::Variable(format!("{entity}.{field}")))?;
+                fields.push(format!("{field}: {value}"));
+            }
+
+            *output += &fields.join(", ");
+            Ok(())
+        }
+        Command::Class(class, fields) => storage.defineClass(class.to_owned(), fields.clone()),
+        Command::New(entity, class, overrides) => {
+            instantiate(storage, entity, class, overrides, output)
+        }
+        Command::List(list) => storage.createVariable(list.to_owned(), Value::List(List::new())),
+        Command::Push(list, item, quantity) => {
+            let (item, quantity) = evaluateItem(storage, item, quantity)?;
+            modifyList(storage, list, |items| {
+                items.push(&item, quantity).ok_or_else(|| overflow(list))
+            })
+        }
+        Command::Remove(list, item, quantity) => {
+            let (item, quantity) = evaluateItem(storage, item, quantity)?;
+            modifyList(storage, list, |items| {
+                items.remove(&item, quantity).ok_or_else(|| {
+                    RunErr::MissingItem(list.to_owned(), describeItem(&item, quantity))
+                })
+            })
+        }
+        Command::Config(setting) => {
+            storage.applySetting(*setting);
+            Ok(())
+        }
+        Command::If(_)
+        | Command::ElseIf(_)
+        | Command::Else
+        | Command::Repeat(_, _)
+        | Command::While(_, _)
+        | Command::Def(_, _)
+        | Command::End => Err(RunErr::UnexpectedBlockMarker(command.keyword().to_string())),
+        Command::Call(name, arguments) => callMacro(storage, name, arguments, output),
+        Command::Pool(pool, max) => {
+            let max = evaluateNumber(storage, max)?;
+            if max <= Number::from(0) {
+                return Err(RunErr::InvalidMaximum(pool.to_owned()));
+            }
+            storage.createVariable(pool.to_owned(), Value::Pool(Pool::new(max)))
+        }
+        Command::Effect(name, target, amount, duration, unit) => {
+            let amount = evaluateNumber(storage, amount)?;
+            let remaining = evaluateDuration(storage, duration)?;
+
+            // Starting an effect again only renews it, instead of stacking it on top of itself.
+            if let Some(renewed) = storage.takeEffect(name, target) {
+                undoEffect(storage, &renewed)?;
             }
 
-            // If the `Content` is a command, execute it.
-            Content::Command(Command::Let(variable, val)) => storage.createVariable(variable, val),
+            let change = if isPool(storage, target)? {
+                let before = poolLevel(storage, target)?;
+                runCommand(
+                    storage,
+                    &Command::Add(target.to_owned(), Expression::Value(amount.clone())),
+                    output,
+                )?;
+                let change = poolLevel(storage, target)?
+                    .sub(&before)
+                    .ok_or_else(|| RunErr::ArithmeticOverflow(target.to_owned(), String::new()))?;
+                Some(change)
+            } else {
+                modifiableBase(storage, target)?;
+                let modifier = Modifier {
+                    source: name.to_owned(),
+                    origin: Origin::Effect,
+                    amount: amount.clone(),
+                    percent: false,
+                };
+                storage.addModifier(target.to_owned(), modifier);
+                None
+            };
 
-            Content::Command(Command::Set(variable, val)) => {
-                storage.modifyVariable(variable, |_| val)
+            storage.addEffect(Effect {
+                name: name.to_owned(),
+                target: target.to_owned(),
+                amount,
+                remaining,
+                unit: *unit,
+                change,
+            });
+            Ok(())
+        }
+        Command::Tick(ticks) => {
+            let ticks = evaluateDuration(storage, ticks)?;
+            expire(storage, TimeUnit::Ticks, ticks)
+        }
+        Command::Chapter => endChapter(storage),
+        Command::Effects(target) => {
+            let effects: Vec<String> = storage
+                .getEffects(target.as_deref())
+                .iter()
+                .map(|effect| effect.to_string())
+                .collect();
+
+            *output += &effects.join(", ");
+            Ok(())
+        }
+        Command::Curve(experience, curve) => {
+            storage.defineCurve(experience.to_owned(), curve.clone());
+            Ok(())
+        }
+        Command::Modifier(stat, source, amount, percent) => {
+            modifiableBase(storage, stat)?;
+            let modifier = Modifier {
+                source: source.to_owned(),
+                origin: Origin::Command,
+                amount: evaluateNumber(storage, amount)?,
+                percent: *percent,
+            };
+            storage.addModifier(stat.to_owned(), modifier);
+            Ok(())
+        }
+        Command::Revoke(source) => storage.revokeModifier(source),
+        Command::Explain(stat) => {
+            let base = modifiableBase(storage, stat)?;
+            let total = applyModifiers(storage, stat, base.clone())?;
+
+            *output += &format!("{stat} {total} = {base} base");
+            for modifier in storage.getModifiers(stat) {
+                *output += &format!(" {modifier}");
+            }
+            Ok(())
+        }
+        Command::Item(item, slot, bonuses) => {
+            storage.defineItem(item.to_owned(), slot.to_owned(), bonuses.clone())
+        }
+        Command::Equip(entity, item) => {
+            storage.getFields(entity)?;
+            let (slot, bonuses) = storage.getItem(item)?;
+            let slot = format!("{entity}.{slot}");
+
+            // Everything is checked before anything is changed, so the item is either equipped with all of its
+            // bonuses, or not at all.
+            let mut modifiers: Vec<(String, Modifier)> = Vec::new();
+            for (stat, amount) in bonuses {
+                let stat = format!("{entity}.{stat}");
+                modifiableBase(storage, &stat)?;
+
+                let modifier = Modifier {
+                    source: item.to_owned(),
+                    origin: Origin::Item,
+                    amount: evaluateNumber(storage, amount)?,
+                    percent: false,
+                };
+                modifiers.push((stat, modifier));
+            }
+
+            storage.equip(slot, item.to_owned())?;
+            for (stat, modifier) in modifiers {
+                storage.addModifier(stat, modifier);
+            }
+            Ok(())
+        }
+        Command::Unequip(entity, slot) => {
+            let item = storage.unequip(&format!("{entity}.{slot}"))?;
+            let stats: Vec<String> = storage
+                .getItem(&item)?
+                .1
+                .iter()
+                .map(|(stat, _)| format!("{entity}.{stat}"))
+                .collect();
+
+            for stat in stats {
+                storage.removeModifier(&stat, &item, Origin::Item);
             }
-            Content::Command(Command::Add(variable, val)) => {
-                storage.modifyVariable(variable, |og| og + val)
+            Ok(())
+        }
+        Command::Points(pool, amount) => {
+            let points = evaluateNumber(storage, amount)?;
+            if points.isNegative() {
+                return Err(RunErr::InvalidQuantity(amount.to_string()));
+            }
+            storage.createPoints(pool.to_owned(), points)
+        }
+        Command::Allocate(stat, pool, amount) => {
+            let points = evaluateNumber(storage, amount)?;
+            if points.isFractional() || points.isNegative() || points.isZero() {
+                return Err(RunErr::InvalidQuantity(amount.to_string()));
+            }
+            if !storage.isPoints(pool) {
+                return Err(RunErr::NotPoints(pool.to_owned()));
             }
 
-            Content::Command(Command::Subtract(variable, val)) => {
-                storage.modifyVariable(variable, |og| og - val)
+            // The stat is checked before the points are taken, so a failure can't leave them half spent. The points
+            // are counted without any modifiers, as those can't be spent.
+            modifiableBase(storage, stat)?;
+            let available = modifiableBase(storage, pool)?;
+            if available < points {
+                return Err(RunErr::NotEnoughPoints(
+                    pool.to_owned(),
+                    points.to_string(),
+                    available.to_string(),
+                ));
             }
-            Content::Command(Command::Write(variable)) => match storage.getValue(variable) {
-                Ok(val) => {
-                    output += &val.to_string();
-                    Ok(())
+
+            // Everything allocated to a stat is a single modifier, which works on derived stats as well.
+            let allocated = storage
+                .getModifiers(stat)
+                .iter()
+                .find(|modifier| modifier.origin == Origin::Allocation)
+                .map_or(Number::from(0), |modifier| modifier.amount.clone());
+            let total = fitting(storage.settings().numbers, allocated.add(&points))
+                .ok_or_else(|| overflow(stat))?;
+
+            runCommand(
+                storage,
+                &Command::Subtract(pool.to_owned(), Expression::Value(points)),
+                output,
+            )?;
+            storage.addModifier(
+                stat.to_owned(),
+                Modifier {
+                    source: String::from("allocate"),
+                    origin: Origin::Allocation,
+                    amount: total,
+                    percent: false,
+                },
+            );
+            Ok(())
+        }
+        Command::Skill(skill, max, prerequisites) => {
+            let max = evaluateNumber(storage, max)?
+                .toWhole()
+                .and_then(|max| u32::try_from(max).ok())
+                .filter(|max| *max > 0)
+                .ok_or_else(|| RunErr::InvalidQuantity(max.to_string()))?;
+            storage.defineSkill(skill.to_owned(), max, prerequisites.clone())
+        }
+        Command::Learn(entity, skill) | Command::Rankup(entity, skill) => {
+            storage.getFields(entity)?;
+            let (max, _) = storage.getSkill(skill)?;
+            let rank = storage.getRank(entity, skill);
+
+            match command {
+                Command::Learn(_, _) if rank > 0 => {
+                    return Err(RunErr::SkillAlreadyLearned(
+                        entity.to_owned(),
+                        skill.to_owned(),
+                    ))
+                }
+                Command::Rankup(_, _) if rank == 0 => {
+                    return Err(RunErr::SkillNotLearned(entity.to_owned(), skill.to_owned()))
                 }
-                Err(err) => Err(err),
+                _ => {}
+            }
+            if rank >= max {
+                return Err(RunErr::MaxRankReached(skill.to_owned(), max));
+            }
+
+            checkPrerequisites(storage, entity, skill)?;
+            storage.setRank(entity, skill, rank + 1);
+            Ok(())
+        }
+        Command::Skills(entity) => {
+            storage.getFields(entity)?;
+            let mut skills: Vec<String> = Vec::new();
+            for (skill, rank) in storage.getSkills(entity) {
+                let (max, _) = storage.getSkill(skill)?;
+                skills.push(format!("{skill} {rank}/{max}"));
+            }
+
+            *output += &skills.join(", ");
+            Ok(())
+        }
+        Command::Seed(seed) => {
+            storage.rng().reseed(*seed);
+            Ok(())
+        }
+        Command::Roll(expression) => {
+            let start = storage.rng().logged();
+            let result = evaluateNumber(storage, expression)?;
+            storage
+                .rng()
+                .combine(start, expression.to_string(), result.clone());
+
+            *output += &result.to_string();
+            Ok(())
+        }
+        Command::Write(expression, format) => {
+            // Strings are written as they are.
+            *output += &match format {
+                Format::Plain => evaluate(storage, expression)?.to_string(),
+                Format::Decimals(decimals) => evaluateNumber(storage, expression)?
+                    .format(*decimals, storage.settings().rounding)
+                    .ok_or_else(|| overflow(&expression.to_string()))?,
+                Format::Bullets => match evaluate(storage, expression)? {
+                    Value::List(list) => list
+                        .entries()
+                        .iter()
+                        .map(|entry| format!("- {entry}"))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                    other => return Err(mismatch(expression, &other, "a list")),
+                },
+            };
+            Ok(())
+        }
+    }
+}
+
+/// Runs the contents of the macro, with the arguments written in place of the parameters.
+fn callMacro(
+    storage: &mut Storage,
+    name: &str,
+    arguments: &[Expression],
+    output: &mut String,
+) -> Result<(), RunErr> {
+    let (parameters, contents) = storage.getMacro(name)?;
+    if parameters.len() != arguments.len() {
+        return Err(RunErr::WrongArity(
+            name.to_string(),
+            match parameters.len() {
+                1 => String::from("1 argument"),
+                count => format!("{count} arguments"),
             },
-        }?
+        ));
     }
 
-    Ok(output)
+    // An argument which is only a name stands for that variable or entity, so the macro can change it. Anything
+    // else is evaluated once, right here, so that the dice in `heal(1d20)` are rolled once, however many times
+    // the macro uses the parameter.
+    let arguments = arguments
+        .iter()
+        .map(|argument| match argument {
+            Expression::Variable(_) => Ok(argument.clone()),
+            _ => Ok(match evaluate(storage, argument)? {
+                Value::Number(number) => Expression::Value(number),
+                Value::Str(text) => Expression::Str(text),
+                _ => argument.clone(),
+            }),
+        })
+        .collect::<Result<Vec<Expression>, RunErr>>()?;
+
+    let bound: macros::Arguments = parameters
+        .iter()
+        .map(String::as_str)
+        .zip(&arguments)
+        .collect();
+    let expanded = macros::expand(contents, &bound)?;
+
+    storage.enterMacro()?;
+    let result = expanded
+        .iter()
+        .try_for_each(|content| runContent(storage, content, output));
+    storage.leaveMacro();
+
+    result
+}
+
+/// Raises the level for as long as the experience reaches the threshold of the next one, calling the hook after
+/// every level gained. The experience is never taken away, so `add xp 500` can be worth several levels at once.
+fn levelUp(storage: &mut Storage, experience: &str, output: &mut String) -> Result<(), RunErr> {
+    let Some(curve) = storage.getCurve(experience).cloned() else {
+        return Ok(());
+    };
+
+    // A curve whose thresholds don't grow, like `level * 0`, would level up forever.
+    let limit = storage.settings().iterations;
+    let mut gained: u32 = 0;
+
+    loop {
+        let next = evaluateNumber(storage, &Expression::Variable(curve.level.to_owned()))?
+            .add(&Number::from(1))
+            .ok_or_else(|| RunErr::ArithmeticOverflow(curve.level.to_owned(), String::new()))?;
+
+        let Some(threshold) = thresholdOf(storage, &curve, &next)? else {
+            return Ok(());
+        };
+        if evaluateNumber(storage, &Expression::Variable(experience.to_string()))? < threshold {
+            return Ok(());
+        }
+
+        if gained == limit {
+            return Err(RunErr::EndlessCurve(experience.to_string(), limit));
+        }
+        gained += 1;
+
+        runCommand(
+            storage,
+            &Command::Set(curve.level.to_owned(), Expression::Value(next)),
+            output,
+        )?;
+        if let Some((hook, arguments)) = &curve.hook {
+            callMacro(storage, hook, arguments, output)?;
+        }
+    }
+}
+
+/// The total experience needed to reach the level, or `None` if the table doesn't go that far.
+fn thresholdOf(storage: &Storage, curve: &Curve, level: &Number) -> Result<Option<Number>, RunErr> {
+    match &curve.thresholds {
+        Thresholds::Formula(formula) => {
+            let formula = formula.replaceVariables(&|variable| {
+                Ok::<Expression, RunErr>(if variable == curve.level {
+                    Expression::Value(level.clone())
+                } else {
+                    Expression::Variable(variable.to_string())
+                })
+            })?;
+            evaluateNumber(storage, &formula).map(Some)
+        }
+        Thresholds::Table(table) => {
+            let index = level
+                .toWhole()
+                .and_then(|level| usize::try_from(level - 1).ok())
+                .ok_or_else(|| RunErr::InvalidLevel(level.to_string()))?;
+
+            table
+                .get(index)
+                .map(|threshold| evaluateNumber(storage, threshold))
+                .transpose()
+        }
+    }
+}
+
+/// Checks the prerequisites of the skill, both for learning it and for every rank after that.
+///
+/// A prerequisite naming another skill means it has to be learned first. Any other one is a condition, in which the
+/// fields of the entity can be written without its name, like `int >= 20` for `hero.int >= 20`.
+fn checkPrerequisites(storage: &Storage, entity: &str, skill: &str) -> Result<(), RunErr> {
+    let (_, prerequisites) = storage.getSkill(skill)?;
+    let fields = storage.getFields(entity)?;
+    let scope = |variable: &str| {
+        Ok::<Expression, RunErr>(Expression::Variable(
+            if fields.iter().any(|field| field == variable) {
+                format!("{entity}.{variable}")
+            } else {
+                variable.to_string()
+            },
+        ))
+    };
+
+    for prerequisite in prerequisites {
+        let (met, description) = match prerequisite {
+            Expression::Variable(required) | Expression::Str(required)
+                if storage.getSkill(required).is_ok() =>
+            {
+                (storage.getRank(entity, required) > 0, required.to_owned())
+            }
+            // A name which is neither a skill nor a variable is most likely a skill which was never defined, or
+            // was misspelled, so the error says which prerequisite of which skill it is.
+            Expression::Str(required) => {
+                return Err(RunErr::UnknownPrerequisite(
+                    skill.to_string(),
+                    required.to_owned(),
+                ))
+            }
+            condition => (
+                evaluateNumber(storage, &condition.replaceVariables(&scope)?)
+                    .map_err(|err| match err {
+                        RunErr::TriedToGetNonexistentVariable => {
+                            RunErr::UnknownPrerequisite(skill.to_string(), condition.to_string())
+                        }
+                        other => other,
+                    })?
+                    .isTrue(),
+                condition.to_string(),
+            ),
+        };
+
+        if !met {
+            return Err(RunErr::PrerequisiteNotMet(
+                entity.to_string(),
+                skill.to_string(),
+                description,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Counts down the effects, undoing the ones which ran out.
+fn expire(storage: &mut Storage, unit: TimeUnit, amount: u32) -> Result<(), RunErr> {
+    for effect in storage.advance(unit, amount) {
+        undoEffect(storage, &effect)?;
+    }
+    Ok(())
+}
+
+/// Takes back what the effect did, which is either its modifier, or the change it made to a pool.
+fn undoEffect(storage: &mut Storage, effect: &Effect) -> Result<(), RunErr> {
+    match &effect.change {
+        Some(change) => runCommand(
+            storage,
+            &Command::Subtract(effect.target.to_owned(), Expression::Value(change.clone())),
+            &mut String::new(),
+        ),
+        None => {
+            storage.removeModifier(&effect.target, &effect.name, Origin::Effect);
+            Ok(())
+        }
+    }
+}
+
+/// Whether the variable is a pool, or the maximum of one.
+fn isPool(storage: &Storage, variable: &str) -> Result<bool, RunErr> {
+    Ok(maximumOf(storage, variable).is_some()
+        || matches!(baseValue(storage, variable)?, Value::Pool(_)))
+}
+
+/// How full the pool is, or its maximum for `hp.max`.
+fn poolLevel(storage: &Storage, variable: &str) -> Result<Number, RunErr> {
+    match baseValue(storage, variable)? {
+        Value::Pool(pool) => Ok(pool.current().clone()),
+        Value::Number(max) => Ok(max),
+        other => Err(mismatch(
+            &Expression::Variable(variable.to_owned()),
+            &other,
+            "a number",
+        )),
+    }
+}
+
+/// Ends the chapter, for the effects which last for chapters. It's called between the input files, as each
+/// of them is a chapter of its own, and by the `chapter` command.
+pub fn endChapter(storage: &mut Storage) -> Result<(), RunErr> {
+    expire(storage, TimeUnit::Chapters, 1)
+}
+
+/// Evaluates how long an effect lasts, or how many ticks pass, which has to be a positive whole number.
+fn evaluateDuration(storage: &Storage, duration: &Expression) -> Result<u32, RunErr> {
+    evaluateNumber(storage, duration)?
+        .toWhole()
+        .and_then(|duration| u32::try_from(duration).ok())
+        .filter(|duration| *duration > 0)
+        .ok_or_else(|| RunErr::InvalidDuration(duration.to_string()))
+}
+
+/// Returns the name of the pool if the variable is its maximum, like `hp.max`.
+fn maximumOf<'a>(storage: &Storage, variable: &'a str) -> Option<&'a str> {
+    let pool = variable.strip_suffix(".max")?;
+    matches!(storage.getValue(pool.to_owned()), Ok(Value::Pool(_))).then_some(pool)
+}
+
+/// Sets, raises or lowers the maximum of a pool, refilling it if the settings say so.
+fn changeMaximum(
+    storage: &mut Storage,
+    command: &Command,
+    variable: &str,
+    expression: &Expression,
+) -> Result<(), RunErr> {
+    let overflow = || RunErr::ArithmeticOverflow(variable.to_owned(), String::new());
+    let settings = *storage.settings();
+    let pool = maximumOf(storage, variable).unwrap_or(variable);
+
+    let val = evaluateNumber(storage, expression)?;
+    storage.modifyVariable(pool.to_owned(), |og| {
+        let Value::Pool(og) = og else {
+            return Err(mismatch(
+                &Expression::Variable(pool.to_owned()),
+                &og,
+                "a pool",
+            ));
+        };
+
+        let max = match command {
+            Command::Add(_, _) => og.max().add(&val),
+            Command::Subtract(_, _) => og.max().sub(&val),
+            _ => Some(val),
+        };
+        let max = fitting(settings.numbers, max).ok_or_else(overflow)?;
+        if max <= Number::from(0) {
+            return Err(RunErr::InvalidMaximum(pool.to_owned()));
+        }
+
+        og.withMax(max, &settings)
+            .filter(|pool| fitting(settings.numbers, Some(pool.current().clone())).is_some())
+            .map(Value::Pool)
+            .ok_or_else(overflow)
+    })
+}
+
+/// Whether evaluating the expression rolls anything, either dice or a function like `random`.
+fn rolls(expression: &Expression) -> bool {
+    match expression {
+        Expression::Dice(_, _) => true,
+        Expression::Call(name, arguments) => {
+            matches!(
+                builtins::lookup(name).map(|builtin| builtin.body),
+                Some(builtins::Body::Random(_))
+            ) || arguments.iter().any(rolls)
+        }
+        Expression::Binary(_, lhand, rhand) => rolls(lhand) || rolls(rhand),
+        Expression::Unary(_, expr) => rolls(expr),
+        Expression::Value(_) | Expression::Str(_) | Expression::Variable(_) => false,
+    }
+}
+
+/// The value of the variable before any modifiers. A derived variable is computed from its expression, and
+/// the parts of a pool are read from the pool.
+fn baseValue(storage: &Storage, variable: &str) -> Result<Value, RunErr> {
+    match storage.readDerived(variable, |derived| evaluate(storage, derived)) {
+        Some(value) => value,
+        None => match storage.getValue(variable.to_owned()) {
+            Ok(value) => Ok(value),
+            Err(err) => Ok(Value::Number(poolComponent(storage, variable)?.ok_or(err)?)),
+        },
+    }
+}
+
+/// The base value of a stat which can be modified. Only numbers can be, and the stat has to be there already.
+fn modifiableBase(storage: &Storage, stat: &str) -> Result<Number, RunErr> {
+    match baseValue(storage, stat)? {
+        Value::Number(base) => Ok(base),
+        other => Err(mismatch(
+            &Expression::Variable(stat.to_owned()),
+            &other,
+            "a number",
+        )),
+    }
+}
+
+/// Adds the flat modifiers of the stat to its base value, and then the sum of its percentages.
+fn applyModifiers(storage: &Storage, stat: &str, base: Number) -> Result<Number, RunErr> {
+    let modifiers = storage.getModifiers(stat);
+    if modifiers.is_empty() {
+        return Ok(base);
+    }
+
+    let overflow = || RunErr::ArithmeticOverflow(stat.to_string(), String::new());
+    let numbers = storage.settings().numbers;
+    let mut total = base;
+    let mut percent = Number::from(0);
+
+    for modifier in modifiers {
+        if modifier.percent {
+            percent = percent.add(&modifier.amount).ok_or_else(overflow)?;
+        } else {
+            total = fitting(numbers, total.add(&modifier.amount)).ok_or_else(overflow)?;
+        }
+    }
+
+    if percent.isZero() {
+        return Ok(total);
+    }
+    let bonus = total
+        .mul(&percent.percent().ok_or_else(overflow)?, storage.settings())
+        .ok_or_else(overflow)?;
+    fitting(numbers, total.add(&bonus)).ok_or_else(overflow)
+}
+
+/// Reads the parts of a pool which aren't variables of their own, `hp.max` and `hp.percent`.
+fn poolComponent(storage: &Storage, variable: &str) -> Result<Option<Number>, RunErr> {
+    let Some((pool, component)) = variable.rsplit_once('.') else {
+        return Ok(None);
+    };
+    let Ok(Value::Pool(pool)) = storage.getValue(pool.to_owned()) else {
+        return Ok(None);
+    };
+
+    match component {
+        "max" => Ok(Some(pool.max().clone())),
+        "percent" => pool
+            .percent(storage.settings())
+            .map(Some)
+            .ok_or_else(|| RunErr::ArithmeticOverflow(variable.to_owned(), String::new())),
+        _ => Ok(None),
+    }
+}
+
+/// Evaluates the item given to `push` or `remove`, which has to be a string, and how many of it there are,
+/// which has to be a positive whole number.
+fn evaluateItem(
+    storage: &Storage,
+    item: &Expression,
+    quantity: &Expression,
+) -> Result<(String, i64), RunErr> {
+    let name = match evaluate(storage, item)? {
+        Value::Str(name) => name,
+        other => return Err(mismatch(item, &other, "a string")),
+    };
+    let count = evaluateNumber(storage, quantity)?
+        .toWhole()
+        .filter(|count| *count > 0)
+        .ok_or_else(|| RunErr::InvalidQuantity(quantity.to_string()))?;
+
+    Ok((name, count))
+}
+
+/// Changes the list stored in the variable, failing if the variable holds anything else.
+fn modifyList(
+    storage: &mut Storage,
+    variable: &str,
+    change: impl FnOnce(&mut List) -> Result<(), RunErr>,
+) -> Result<(), RunErr> {
+    storage.modifyVariable(variable.to_owned(), |value| match value {
+        Value::List(mut list) => {
+            change(&mut list)?;
+            Ok(Value::List(list))
+        }
+        other => Err(mismatch(
+            &Expression::Variable(variable.to_owned()),
+            &other,
+            "a list",
+        )),
+    })
+}
+
+/// Only lets the result of an operation through if it fits the numbers the author chose.
+fn fitting(mode: NumberMode, result: Option<Number>) -> Option<Number> {
+    result.filter(|number| mode == NumberMode::Big || !number.isBig())
+}
+
+/// Walks the expression tree and computes its value, reading any variables from the storage.
+///
+/// All of the arithmetic is checked, so a result which doesn't fit results in `RunErr::ArithmeticOverflow`
+/// naming the expression, instead of a panic or a silently wrapped number.
+pub fn evaluate(storage: &Storage, expression: &Expression) -> Result<Value, RunErr> {
+    let overflow = || RunErr::ArithmeticOverflow(expression.to_string(), String::new());
+    let settings = *storage.settings();
+    let number = |expr: &Expression| evaluateNumber(storage, expr);
+
+    let result = match expression {
+        // Values and variables are checked too, as the mode could have been changed after they were written.
+        Expression::Value(val) => Some(val.clone()),
+        Expression::Str(text) => return Ok(Value::Str(text.to_owned())),
+        Expression::Variable(variable) => match baseValue(storage, variable)? {
+            Value::Number(number) => Some(applyModifiers(storage, variable, number)?),
+            text => return Ok(text),
+        },
+        Expression::Call(name, arguments) => {
+            let function =
+                builtins::lookup(name).ok_or(RunErr::UnknownFunction(name.to_owned()))?;
+            function.checkArity(arguments.len())?;
+
+            match function.body {
+                builtins::Body::Numbers(function) => {
+                    let arguments = arguments
+                        .iter()
+                        .map(number)
+                        .collect::<Result<Vec<Number>, RunErr>>()?;
+
+                    function(&arguments, &settings).map_err(|err| match err {
+                        RunErr::DivisionByZero(_) => RunErr::DivisionByZero(expression.to_string()),
+                        RunErr::InvalidBounds(_) => RunErr::InvalidBounds(expression.to_string()),
+                        other => other,
+                    })?
+                }
+                builtins::Body::Random(function) => {
+                    let arguments = arguments
+                        .iter()
+                        .map(number)
+                        .collect::<Result<Vec<Number>, RunErr>>()?;
+
+                    function(&arguments, storage.rng()).map_err(|err| match err {
+                        RunErr::InvalidRange(_) => RunErr::InvalidRange(expression.to_string()),
+                        other => other,
+                    })?
+                }
+                builtins::Body::Items(function) => {
+                    let list = match evaluate(storage, &arguments[0])? {
+                        Value::List(list) => list,
+                        other => return Err(mismatch(&arguments[0], &other, "a list")),
+                    };
+                    let mut items: Vec<String> = Vec::new();
+                    for argument in &arguments[1..] {
+                        match evaluate(storage, argument)? {
+                            Value::Str(item) => items.push(item),
+                            other => return Err(mismatch(argument, &other, "a string")),
+                        }
+                    }
+
+                    Some(function(&list, &items))
+                }
+            }
+        }
+        Expression::Dice(count, sides) => Some(Number::from(
+            storage.rng().roll(*count, *sides).iter().sum::<i64>(),
+        )),
+        Expression::Unary(UnOperation::Minus, expr) => number(expr)?.neg(),
+        Expression::Unary(UnOperation::Not, expr) => Some(Number::from(!number(expr)?.isTrue())),
+        // The right side is only evaluated when it can still change the result, so a condition like
+        // `enemies != 0 && loot / enemies > 5` can guard the division.
+        Expression::Binary(operation @ (BiOperation::And | BiOperation::Or), lhand, rhand) => {
+            let lhand = number(lhand)?.isTrue();
+
+            Some(Number::from(match operation {
+                BiOperation::And => lhand && number(rhand)?.isTrue(),
+                _ => lhand || number(rhand)?.isTrue(),
+            }))
+        }
+        Expression::Binary(operation, lhand_expr, rhand_expr) => {
+            let mut operands = (
+                evaluate(storage, lhand_expr)?,
+                evaluate(storage, rhand_expr)?,
+            );
+            // Pools count as their current value, unless they're being joined with a string.
+            if !matches!(operands, (Value::Str(_), _) | (_, Value::Str(_))) {
+                operands = (operands.0.numeric(), operands.1.numeric());
+            }
+
+            match operands {
+                (Value::Number(lhand), Value::Number(rhand)) => {
+                    calculate(*operation, &lhand, &rhand, &settings).map_err(|err| match err {
+                        RunErr::DivisionByZero(_) => RunErr::DivisionByZero(expression.to_string()),
+                        other => other,
+                    })?
+                }
+                // With a string on either side, adding means joining them, so `"Level " + level` works too.
+                (lhand, rhand) if *operation == BiOperation::Add => {
+                    return Ok(Value::Str(format!("{lhand}{rhand}")));
+                }
+                (Value::Str(lhand), Value::Str(rhand)) if *operation == BiOperation::Equal => {
+                    Some(Number::from(lhand == rhand))
+                }
+                (Value::Str(lhand), Value::Str(rhand)) if *operation == BiOperation::NotEqual => {
+                    Some(Number::from(lhand != rhand))
+                }
+                // Strings can be compared with each other, but nothing else can be done with them. Which side
+                // is the wrong one depends on the operation.
+                (Value::Str(_), rhand)
+                    if matches!(operation, BiOperation::Equal | BiOperation::NotEqual) =>
+                {
+                    return Err(mismatch(rhand_expr, &rhand, "a string"));
+                }
+                (Value::Number(_), rhand) => return Err(mismatch(rhand_expr, &rhand, "a number")),
+                (lhand, _) => return Err(mismatch(lhand_expr, &lhand, "a number")),
+            }
+        }
+    };
+
+    fitting(settings.numbers, result)
+        .map(Value::Number)
+        .ok_or_else(overflow)
+}
+
+/// Evaluates an expression whose value has to be a number, like an operand of arithmetic or a condition.
+pub fn evaluateNumber(storage: &Storage, expression: &Expression) -> Result<Number, RunErr> {
+    match evaluate(storage, expression)?.numeric() {
+        Value::Number(number) => Ok(number),
+        other => Err(mismatch(expression, &other, "a number")),
+    }
+}
+
+fn mismatch(expression: &Expression, value: &Value, expected: &str) -> RunErr {
+    RunErr::TypeMismatch(
+        expression.to_string(),
+        value.typeName().to_string(),
+        expected.to_string(),
+    )
+}
+
+/// Does an operation on two numbers. A division by zero is returned without the expression, which the caller
+/// fills in.
+fn calculate(
+    operation: BiOperation,
+    lhand: &Number,
+    rhand: &Number,
+    settings: &Settings,
+) -> Result<Option<Number>, RunErr> {
+    Ok(match operation {
+        BiOperation::Add => lhand.add(rhand),
+        BiOperation::Subtract => lhand.sub(rhand),
+        BiOperation::Multiply => lhand.mul(rhand, settings),
+        BiOperation::Divide | BiOperation::Modulo if rhand.isZero() => {
+            return Err(RunErr::DivisionByZero(String::new()));
+        }
+        BiOperation::Divide => lhand.div(rhand, settings),
+        BiOperation::Modulo => lhand.rem(rhand, settings),
+        BiOperation::Exponentiate => power(lhand, rhand, settings)?,
+        BiOperation::Less => Some(Number::from(lhand < rhand)),
+        BiOperation::LessOrEqual => Some(Number::from(lhand <= rhand)),
+        BiOperation::Greater => Some(Number::from(lhand > rhand)),
+        BiOperation::GreaterOrEqual => Some(Number::from(lhand >= rhand)),
+        BiOperation::Equal => Some(Number::from(lhand == rhand)),
+        BiOperation::NotEqual => Some(Number::from(lhand != rhand)),
+        BiOperation::And => Some(Number::from(lhand.isTrue() && rhand.isTrue())),
+        BiOperation::Or => Some(Number::from(lhand.isTrue() || rhand.isTrue())),
+    })
+}
+
+/// Raises the base to the exponent, which has to be a whole number that isn't negative.
+/// Returns `None` when the result overflows.
+fn power(base: &Number, exponent: &Number, settings: &Settings) -> Result<Option<Number>, RunErr> {
+    // A negative exponent would need a fraction of unknown precision, so it's an error rather than a silent zero.
+    if exponent.isNegative() {
+        Err(RunErr::NegativeExponent)
+    } else if exponent.isFractional() {
+        Err(RunErr::FractionalExponent)
+    } else {
+        Ok(base.pow(exponent, settings))
+    }
 }
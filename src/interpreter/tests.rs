@@ -1,8 +1,36 @@
 #![allow(unused_imports)]
 
-use crate::types::engine::Storage;
+use crate::types::curve::{Curve, Thresholds};
+use crate::types::effect::TimeUnit;
+use crate::types::engine::{NumberMode, Refill, Rounding, Storage, MAX_DERIVED_DEPTH};
 use crate::types::error::RunErr;
-use crate::types::{Command, Content};
+use crate::types::number::Number;
+use crate::types::value::Value;
+use crate::types::{BiOperation, Command, Content, Expression, Format, Setting, UnOperation};
+
+fn command(command: Command) -> Content {
+    Content::Command(command)
+}
+
+fn value(val: i64) -> Expression {
+    Expression::Value(Number::from(val))
+}
+
+fn variable(name: &str) -> Expression {
+    Expression::Variable(name.to_string())
+}
+
+fn string(text: &str) -> Expression {
+    Expression::Str(text.to_string())
+}
+
+fn binary(operation: BiOperation, lhand: Expression, rhand: Expression) -> Expression {
+    Expression::Binary(operation, Box::new(lhand), Box::new(rhand))
+}
+
+fn write(expression: Expression) -> Content {
+    command(Command::Write(expression, Format::Plain))
+}
 
 #[test]
 fn functionality() {
@@ -12,7 +40,10 @@ fn functionality() {
     assert_eq!(
         super::run(
             &mut storage,
-            vec![Content::Command(Command::Add(String::new(), 1))]
+            vec![Content::Command(Command::Add(
+                String::new(),
+                Expression::Value(Number::Int(1))
+            ))]
         ),
         Err(RunErr::TriedToModifyNonexistentVariable)
     );
@@ -21,7 +52,10 @@ fn functionality() {
     assert_eq!(
         super::run(
             &mut storage,
-            vec![Content::Command(Command::Write(String::new()))]
+            vec![Content::Command(Command::Write(
+                Expression::Variable(String::new()),
+                Format::Plain
+            ))]
         ),
         Err(RunErr::TriedToGetNonexistentVariable)
     );
@@ -29,30 +63,2707 @@ fn functionality() {
     // Test if a viable series of commands (using all of the currently available ones)
     // will result in the predicted output
     let commands: Vec<Content> = (vec![
-        Command::Let("test".to_string(), 1),
-        Command::Write("test".to_string()),
-        Command::Add("test".to_string(), 1),
-        Command::Write("test".to_string()),
-        Command::Set("test".to_string(), 10),
-        Command::Write("test".to_string()),
-        Command::Subtract("test".to_string(), 5),
-        Command::Write("test".to_string()),
+        Command::Let("test".to_string(), Expression::Value(Number::Int(1))),
+        Command::Write(Expression::Variable("test".to_string()), Format::Plain),
+        Command::Add("test".to_string(), Expression::Value(Number::Int(1))),
+        Command::Write(Expression::Variable("test".to_string()), Format::Plain),
+        Command::Set("test".to_string(), Expression::Value(Number::Int(10))),
+        Command::Write(Expression::Variable("test".to_string()), Format::Plain),
+        Command::Subtract("test".to_string(), Expression::Value(Number::Int(5))),
+        Command::Write(Expression::Variable("test".to_string()), Format::Plain),
     ])
     .into_iter()
-    .map(|c| Content::Command(c))
+    .map(Content::Command)
     .collect();
 
     assert_eq!(super::run(&mut storage, commands), Ok("12105".to_string()));
 
+    // Test if the values given to commands are evaluated against the storage when the command runs
+    let commands: Vec<Content> = (vec![
+        Command::Let(
+            "max_test".to_string(),
+            Expression::Binary(
+                BiOperation::Multiply,
+                Box::new(Expression::Variable("test".to_string())),
+                Box::new(Expression::Value(Number::Int(3))),
+            ),
+        ),
+        Command::Set(
+            "test".to_string(),
+            Expression::Variable("max_test".to_string()),
+        ),
+        Command::Write(Expression::Variable("test".to_string()), Format::Plain),
+    ])
+    .into_iter()
+    .map(Content::Command)
+    .collect();
+
+    assert_eq!(super::run(&mut storage, commands), Ok("15".to_string()));
+
     // Test if initializing over existing variable will result in an error
     storage.clear();
-    storage.createVariable("test".to_string(), 1).expect("WHAT");
+    storage
+        .createVariable("test".to_string(), Value::Number(Number::Int(1)))
+        .expect("WHAT");
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![Content::Command(Command::Let(
+                String::from("test"),
+                Expression::Value(Number::Int(1))
+            ))]
+        ),
+        Err(RunErr::TriedToInitializeExistingVariable)
+    );
+}
+
+#[test]
+fn expressions() {
+    let mut storage = Storage::new();
+    storage
+        .createVariable("level".to_string(), Value::Number(Number::Int(3)))
+        .expect("Couldn't create the variable");
+
+    let variable = |name: &str| Box::new(Expression::Variable(name.to_string()));
+    let value = |val: i64| Box::new(Expression::Value(Number::Int(val)));
+
+    // level * 2 + 5
+    let expression = Expression::Binary(
+        BiOperation::Add,
+        Box::new(Expression::Binary(
+            BiOperation::Multiply,
+            variable("level"),
+            value(2),
+        )),
+        value(5),
+    );
+    assert_eq!(
+        super::evaluate(&storage, &expression),
+        Ok(Value::Number(Number::Int(11)))
+    );
+
+    // -(2 ^ level) - level
+    let expression = Expression::Binary(
+        BiOperation::Subtract,
+        Box::new(Expression::Unary(
+            UnOperation::Minus,
+            Box::new(Expression::Binary(
+                BiOperation::Exponentiate,
+                value(2),
+                variable("level"),
+            )),
+        )),
+        variable("level"),
+    );
+    assert_eq!(
+        super::evaluate(&storage, &expression),
+        Ok(Value::Number(Number::Int(-11)))
+    );
+
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &Expression::Binary(BiOperation::Exponentiate, value(2), value(-1))
+        ),
+        Err(RunErr::NegativeExponent)
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                Content::Text("Level: ".to_string()),
+                Content::Command(Command::Write(
+                    Expression::Binary(BiOperation::Multiply, variable("level"), value(10)),
+                    Format::Plain
+                ))
+            ]
+        ),
+        Ok("Level: 30".to_string())
+    );
+}
+
+#[test]
+fn derived() {
+    let mut storage = Storage::new();
+
+    let variable = |name: &str| Box::new(Expression::Variable(name.to_string()));
+    let value = |val: i64| Box::new(Expression::Value(Number::Int(val)));
+
+    // strength = base_str + level * 2
+    let strength = Expression::Binary(
+        BiOperation::Add,
+        variable("base_str"),
+        Box::new(Expression::Binary(
+            BiOperation::Multiply,
+            variable("level"),
+            value(2),
+        )),
+    );
+
+    let commands: Vec<Content> = (vec![
+        Command::Let("base_str".to_string(), Expression::Value(Number::Int(10))),
+        Command::Let("level".to_string(), Expression::Value(Number::Int(1))),
+        Command::Derive("strength".to_string(), strength),
+        Command::Write(Expression::Variable("strength".to_string()), Format::Plain),
+        Command::Add("level".to_string(), Expression::Value(Number::Int(4))),
+        Command::Write(Expression::Variable("strength".to_string()), Format::Plain),
+    ])
+    .into_iter()
+    .map(Content::Command)
+    .collect();
+
+    assert_eq!(super::run(&mut storage, commands), Ok("1220".to_string()));
+
+    // Derived variables can't be modified directly
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![Content::Command(Command::Add(
+                "strength".to_string(),
+                Expression::Value(Number::Int(1))
+            ))]
+        ),
+        Err(RunErr::TriedToModifyDerivedVariable("strength".to_string()))
+    );
 
+    // Nor can they be initialized twice
     assert_eq!(
         super::run(
             &mut storage,
-            vec![Content::Command(Command::Let(String::from("test"), 1))]
+            vec![Content::Command(Command::Let(
+                "strength".to_string(),
+                Expression::Value(Number::Int(1))
+            ))]
         ),
         Err(RunErr::TriedToInitializeExistingVariable)
     );
+
+    // A cycle going through other derived variables is caught when it's defined
+    assert_eq!(
+        storage.createDerived("a".to_string(), *variable("b")),
+        Ok(())
+    );
+    assert_eq!(
+        storage.createDerived("b".to_string(), *variable("c")),
+        Ok(())
+    );
+    assert_eq!(
+        storage.createDerived(
+            "c".to_string(),
+            Expression::Binary(BiOperation::Add, variable("level"), variable("a"))
+        ),
+        Err(RunErr::CyclicDependency("c".to_string()))
+    );
+    assert_eq!(
+        storage.createDerived("d".to_string(), *variable("d")),
+        Err(RunErr::CyclicDependency("d".to_string()))
+    );
+
+    // Every derived variable is only computed once until something it depends on changes, so a chain where each
+    // one reads the one before it twice doesn't take twice as long with every link.
+    let mut storage = Storage::new();
+    let read = |name: &str| Expression::Variable(name.to_string());
+    let mut commands = vec![Command::Let("x".to_string(), *value(1))];
+    commands.push(Command::Derive("d0".to_string(), read("x")));
+    for link in 1..=60 {
+        let previous = format!("d{}", link - 1);
+        commands.push(Command::Derive(
+            format!("d{link}"),
+            Expression::Binary(BiOperation::Add, variable(&previous), variable(&previous)),
+        ));
+    }
+    commands.push(Command::Write(read("d60"), Format::Plain));
+    commands.push(Command::Write(
+        Expression::Str(" ".to_string()),
+        Format::Plain,
+    ));
+    commands.push(Command::Set("x".to_string(), *value(2)));
+    commands.push(Command::Write(read("d60"), Format::Plain));
+    assert_eq!(
+        super::run(
+            &mut storage,
+            commands.into_iter().map(Content::Command).collect()
+        ),
+        Ok(format!("{} {}", 1i64 << 60, 1i64 << 61))
+    );
+
+    // Anything else the value depends on throws it away as well: the modifiers of the variables it reads, the
+    // settings, and the variables going away with their entity.
+    let commands = vec![
+        Command::Let("x".to_string(), *value(-7)),
+        Command::Derive(
+            "half".to_string(),
+            Expression::Binary(BiOperation::Divide, variable("x"), value(2)),
+        ),
+        Command::Write(read("half"), Format::Plain),
+        Command::Modifier("x".to_string(), "Curse".to_string(), *value(-2), false),
+        Command::Write(read("half"), Format::Plain),
+        Command::Config(Setting::Rounding(Rounding::Truncate)),
+        Command::Write(read("half"), Format::Plain),
+        Command::Entity("hero".to_string()),
+        Command::Let("hero.str".to_string(), *value(10)),
+        Command::Derive(
+            "twice".to_string(),
+            Expression::Binary(BiOperation::Multiply, variable("hero.str"), value(2)),
+        ),
+        Command::Write(read("twice"), Format::Plain),
+        Command::Delete("hero".to_string()),
+        Command::Entity("hero".to_string()),
+        Command::Let("hero.str".to_string(), *value(3)),
+        Command::Write(read("twice"), Format::Plain),
+    ];
+    assert_eq!(
+        super::run(
+            &mut Storage::new(),
+            commands.into_iter().map(Content::Command).collect()
+        ),
+        Ok("-4-5-4206".to_string())
+    );
+
+    // A chain of derived variables which is too long fails instead of overflowing the stack, even if the variables
+    // at the end of it were already computed.
+    let mut storage = Storage::new();
+    storage
+        .createVariable("c0".to_string(), Value::from(1))
+        .expect("The variable is new");
+    for link in 1..=MAX_DERIVED_DEPTH + 10 {
+        storage
+            .createDerived(format!("c{link}"), read(&format!("c{}", link - 1)))
+            .expect("The chain has no cycles");
+    }
+    assert_eq!(super::evaluate(&storage, &read("c100")), Ok(Value::from(1)));
+    let top = format!("c{}", MAX_DERIVED_DEPTH + 10);
+    assert_eq!(
+        super::evaluate(&storage, &read(&top)),
+        Err(RunErr::DerivedTooDeep(top))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &read(&format!("c{MAX_DERIVED_DEPTH}"))),
+        Ok(Value::from(1))
+    );
+}
+
+#[test]
+fn division() {
+    let mut storage = Storage::new();
+
+    let divide = |storage: &Storage, operation: BiOperation, lhand: i64, rhand: i64| {
+        super::evaluate(
+            storage,
+            &Expression::Binary(
+                operation,
+                Box::new(Expression::Value(Number::Int(lhand))),
+                Box::new(Expression::Value(Number::Int(rhand))),
+            ),
+        )
+    };
+
+    // (dividend, divisor, floor, truncate, round)
+    let cases = [
+        (7, 2, 3, 3, 4),
+        (-7, 2, -4, -3, -4),
+        (7, -2, -4, -3, -4),
+        (-7, -2, 3, 3, 4),
+        (5, 3, 1, 1, 2),
+        (4, 3, 1, 1, 1),
+        (-4, 3, -2, -1, -1),
+        (12, 4, 3, 3, 3),
+        (0, 5, 0, 0, 0),
+    ];
+
+    for (lhand, rhand, floor, truncate, round) in cases {
+        for (rounding, expected) in [
+            (Rounding::Floor, floor),
+            (Rounding::Truncate, truncate),
+            (Rounding::Round, round),
+        ] {
+            storage.applySetting(Setting::Rounding(rounding));
+
+            assert_eq!(
+                divide(&storage, BiOperation::Divide, lhand, rhand),
+                Ok(Value::Number(Number::Int(expected))),
+                "{lhand} / {rhand} with {rounding:?}"
+            );
+
+            // The remainder always matches the rounding of the division.
+            assert_eq!(
+                divide(&storage, BiOperation::Modulo, lhand, rhand),
+                Ok(Value::Number(Number::Int(lhand - rhand * expected))),
+                "{lhand} % {rhand} with {rounding:?}"
+            );
+        }
+    }
+
+    assert_eq!(
+        divide(&storage, BiOperation::Divide, 1, 0),
+        Err(RunErr::DivisionByZero("(1 / 0)".to_string()))
+    );
+    assert_eq!(
+        divide(&storage, BiOperation::Modulo, 1, 0),
+        Err(RunErr::DivisionByZero("(1 % 0)".to_string()))
+    );
+
+    // The rounding can be changed with a command while the file is interpreted.
+    storage
+        .createVariable("level".to_string(), Value::Number(Number::Int(-9)))
+        .expect("");
+    let level_div_5 = Expression::Binary(
+        BiOperation::Divide,
+        Box::new(Expression::Variable("level".to_string())),
+        Box::new(Expression::Value(Number::Int(5))),
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                Content::Command(Command::Config(Setting::Rounding(Rounding::Floor))),
+                Content::Command(Command::Write(level_div_5.clone(), Format::Plain)),
+                Content::Text(" ".to_string()),
+                Content::Command(Command::Config(Setting::Rounding(Rounding::Truncate))),
+                Content::Command(Command::Write(level_div_5, Format::Plain)),
+            ]
+        ),
+        Ok("-2 -1".to_string())
+    );
+}
+
+#[test]
+fn overflow() {
+    let mut storage = Storage::new();
+    storage
+        .createVariable("gold".to_string(), Value::Number(Number::Int(i64::MAX - 1)))
+        .expect("");
+    storage
+        .createVariable("debt".to_string(), Value::Number(Number::Int(i64::MIN)))
+        .expect("");
+
+    let command = |command: Command| vec![Content::Command(command)];
+    let value = |val: i64| Box::new(Expression::Value(Number::Int(val)));
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            command(Command::Add(
+                "gold".to_string(),
+                Expression::Value(Number::Int(2))
+            ))
+        ),
+        Err(RunErr::ArithmeticOverflow(
+            "gold".to_string(),
+            "add".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            command(Command::Subtract(
+                "debt".to_string(),
+                Expression::Value(Number::Int(1))
+            ))
+        ),
+        Err(RunErr::ArithmeticOverflow(
+            "debt".to_string(),
+            "subtract".to_string()
+        ))
+    );
+
+    // A failed command leaves the variable as it was
+    assert_eq!(
+        storage.getValue("gold".to_string()),
+        Ok(Value::Number(Number::Int(i64::MAX - 1)))
+    );
+
+    // An overflow while computing the value is also reported with the variable being modified
+    assert_eq!(
+        super::run(
+            &mut storage,
+            command(Command::Set(
+                "gold".to_string(),
+                Expression::Binary(BiOperation::Exponentiate, value(10), value(20))
+            ))
+        ),
+        Err(RunErr::ArithmeticOverflow(
+            "gold".to_string(),
+            "set".to_string()
+        ))
+    );
+
+    // Without a variable, the expression which overflowed is named instead
+    let cases = [
+        (BiOperation::Add, i64::MAX, 1),
+        (BiOperation::Subtract, i64::MIN, 1),
+        (BiOperation::Multiply, 4294967296, 4294967296),
+        (BiOperation::Divide, i64::MIN, -1),
+        (BiOperation::Exponentiate, 2, 63),
+    ];
+    for (operation, lhand, rhand) in cases {
+        let expression = Expression::Binary(operation, value(lhand), value(rhand));
+
+        assert_eq!(
+            super::run(
+                &mut storage,
+                command(Command::Write(expression.clone(), Format::Plain))
+            ),
+            Err(RunErr::ArithmeticOverflow(
+                expression.to_string(),
+                "write".to_string()
+            ))
+        );
+    }
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            command(Command::Write(
+                Expression::Unary(
+                    UnOperation::Minus,
+                    Box::new(Expression::Variable("debt".to_string()))
+                ),
+                Format::Plain
+            ))
+        ),
+        Err(RunErr::ArithmeticOverflow(
+            "(-debt)".to_string(),
+            "write".to_string()
+        ))
+    );
+}
+
+#[test]
+fn bigNumbers() {
+    let mut storage = Storage::new();
+
+    let value = |digits: &str| Expression::Value(Number::parse(digits).expect("Not a number"));
+    let xp = || Expression::Variable("xp".to_string());
+
+    // By default, numbers are 64-bit, so a literal which doesn't fit is an overflow.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Let(
+                "xp".to_string(),
+                value("99999999999999999999")
+            ))]
+        ),
+        Err(RunErr::ArithmeticOverflow(
+            "xp".to_string(),
+            "let".to_string()
+        ))
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Config(Setting::Numbers(NumberMode::Big))),
+                command(Command::Let(
+                    "xp".to_string(),
+                    value("99999999999999999999")
+                )),
+                command(Command::Add("xp".to_string(), value("1"))),
+                command(Command::Write(xp(), Format::Plain)),
+                Content::Text(" ".to_string()),
+                command(Command::Write(
+                    Expression::Binary(
+                        BiOperation::Divide,
+                        Box::new(xp()),
+                        Box::new(value("100000000000"))
+                    ),
+                    Format::Plain
+                )),
+                Content::Text(" ".to_string()),
+                command(Command::Write(
+                    Expression::Binary(
+                        BiOperation::Exponentiate,
+                        Box::new(value("2")),
+                        Box::new(value("64"))
+                    ),
+                    Format::Plain
+                )),
+            ]
+        ),
+        Ok("100000000000000000000 1000000000 18446744073709551616".to_string())
+    );
+
+    // Switching back to fixed numbers makes the big values unreadable, but not lost.
+    storage.applySetting(Setting::Numbers(NumberMode::Fixed));
+    assert_eq!(
+        super::evaluate(&storage, &xp()),
+        Err(RunErr::ArithmeticOverflow("xp".to_string(), String::new()))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &Expression::Binary(
+                BiOperation::Divide,
+                Box::new(xp()),
+                Box::new(value("100000000000"))
+            )
+        ),
+        Err(RunErr::ArithmeticOverflow("xp".to_string(), String::new()))
+    );
+}
+
+#[test]
+fn decimals() {
+    let mut storage = Storage::new();
+
+    let value = |text: &str| Expression::Value(Number::parse(text).expect("Not a number"));
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("crit".to_string(), value("0.15"))),
+                command(Command::Let("damage".to_string(), value("200"))),
+                command(Command::Add("crit".to_string(), value("0.05"))),
+                command(Command::Write(variable("crit"), Format::Plain)),
+                Content::Text(" ".to_string()),
+                command(Command::Write(
+                    binary(BiOperation::Multiply, variable("damage"), variable("crit")),
+                    Format::Plain
+                )),
+                Content::Text(" ".to_string()),
+                command(Command::Write(variable("crit"), Format::Decimals(3))),
+                Content::Text(" ".to_string()),
+                command(Command::Write(
+                    binary(BiOperation::Divide, value("1"), value("3.0")),
+                    Format::Plain
+                )),
+            ]
+        ),
+        Ok("0.2 40 0.200 0.3333".to_string())
+    );
+
+    // The precision and rounding are both configurable.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Config(Setting::Precision(2))),
+                command(Command::Config(Setting::Rounding(Rounding::Round))),
+                command(Command::Write(
+                    binary(BiOperation::Divide, value("2"), value("3.0")),
+                    Format::Plain
+                )),
+                Content::Text(" ".to_string()),
+                command(Command::Write(value("2.345"), Format::Decimals(2))),
+            ]
+        ),
+        Ok("0.67 2.35".to_string())
+    );
+
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::Exponentiate, value("4"), value("0.5"))
+        ),
+        Err(RunErr::FractionalExponent)
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::Exponentiate, value("1.5"), value("2.0"))
+        ),
+        Ok(Value::Number(Number::parse("2.25").expect("Not a number")))
+    );
+}
+
+#[test]
+fn conditions() {
+    let mut storage = Storage::new();
+    storage
+        .createVariable("hp".to_string(), Value::Number(Number::from(0)))
+        .expect("");
+    storage
+        .createVariable("level".to_string(), Value::Number(Number::from(12)))
+        .expect("");
+
+    let evaluate = |expression: &Expression| super::evaluate(&storage, expression);
+
+    assert_eq!(
+        evaluate(&binary(BiOperation::LessOrEqual, variable("hp"), value(0))),
+        Ok(Value::Number(Number::from(1)))
+    );
+    assert_eq!(
+        evaluate(&binary(BiOperation::Greater, variable("level"), value(12))),
+        Ok(Value::Number(Number::from(0)))
+    );
+    assert_eq!(
+        evaluate(&binary(
+            BiOperation::Equal,
+            value(2),
+            Expression::Value(Number::parse("2.0").expect(""))
+        )),
+        Ok(Value::Number(Number::from(1)))
+    );
+
+    // Any number other than zero is true, and the result is always 1 or 0.
+    assert_eq!(
+        evaluate(&binary(BiOperation::And, value(-3), variable("level"))),
+        Ok(Value::Number(Number::from(1)))
+    );
+    assert_eq!(
+        evaluate(&binary(BiOperation::Or, variable("hp"), value(0))),
+        Ok(Value::Number(Number::from(0)))
+    );
+    assert_eq!(
+        evaluate(&Expression::Unary(
+            UnOperation::Not,
+            Box::new(variable("hp"))
+        )),
+        Ok(Value::Number(Number::from(1)))
+    );
+
+    // The right side is skipped when the left one already decides the result.
+    let division = binary(BiOperation::Divide, value(10), variable("hp"));
+    assert_eq!(
+        evaluate(&binary(
+            BiOperation::And,
+            binary(BiOperation::NotEqual, variable("hp"), value(0)),
+            division.clone()
+        )),
+        Ok(Value::Number(Number::from(0)))
+    );
+    assert_eq!(
+        evaluate(&binary(BiOperation::Or, value(1), variable("missing"))),
+        Ok(Value::Number(Number::from(1)))
+    );
+    assert_eq!(
+        evaluate(&binary(BiOperation::Or, value(0), division)),
+        Err(RunErr::DivisionByZero("(10 / hp)".to_string()))
+    );
+}
+
+#[test]
+fn conditionalBlocks() {
+    let text = |t: &str| Content::Text(t.to_string());
+    let atLeast = |name: &str, val: i64| {
+        Expression::Binary(
+            BiOperation::GreaterOrEqual,
+            Box::new(variable(name)),
+            Box::new(Expression::Value(Number::from(val))),
+        )
+    };
+    let block = Content::Conditional(
+        vec![
+            (atLeast("int", 20), vec![text("[Skill Unlocked: Fireball]")]),
+            (
+                atLeast("int", 10),
+                vec![
+                    Content::Command(Command::Add(
+                        "int".to_string(),
+                        Expression::Value(Number::from(5)),
+                    )),
+                    text("Almost there"),
+                ],
+            ),
+        ],
+        vec![text("Nothing yet")],
+    );
+
+    let mut storage = Storage::new();
+    let mut runWith = |int: i64| {
+        storage.clear();
+        storage
+            .createVariable("int".to_string(), Value::Number(Number::from(int)))
+            .expect("");
+        super::run(
+            &mut storage,
+            vec![
+                block.clone(),
+                text(" "),
+                Content::Command(Command::Write(variable("int"), Format::Plain)),
+            ],
+        )
+    };
+
+    assert_eq!(runWith(25), Ok("[Skill Unlocked: Fireball] 25".to_string()));
+    // Only the taken branch is run, so the commands in the others don't change anything.
+    assert_eq!(runWith(12), Ok("Almost there 17".to_string()));
+    assert_eq!(runWith(3), Ok("Nothing yet 3".to_string()));
+
+    assert_eq!(
+        super::run(&mut Storage::new(), vec![block]),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::run(&mut Storage::new(), vec![Content::Command(Command::End)]),
+        Err(RunErr::UnexpectedBlockMarker("end".to_string()))
+    );
+}
+
+#[test]
+fn loops() {
+    let text = |t: &str| Content::Text(t.to_string());
+
+    // Trained for 3 days, +1 end each day and +1 vit every 2nd day.
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("end".to_string(), value(10))),
+                command(Command::Let("vit".to_string(), value(5))),
+                Content::Repeat(
+                    value(3),
+                    Some("day".to_string()),
+                    vec![
+                        command(Command::Add("end".to_string(), value(1))),
+                        Content::Conditional(
+                            vec![(
+                                binary(
+                                    BiOperation::Equal,
+                                    binary(BiOperation::Modulo, variable("day"), value(2)),
+                                    value(0),
+                                ),
+                                vec![command(Command::Add("vit".to_string(), value(1)))],
+                            )],
+                            vec![],
+                        ),
+                        text("Day "),
+                        command(Command::Write(variable("day"), Format::Plain)),
+                        text(": "),
+                        command(Command::Write(variable("end"), Format::Plain)),
+                        text("/"),
+                        command(Command::Write(variable("vit"), Format::Plain)),
+                        text(". "),
+                    ],
+                ),
+            ]
+        ),
+        Ok("Day 1: 11/5. Day 2: 12/6. Day 3: 13/6. ".to_string())
+    );
+
+    // The index is gone once the loop is over.
+    assert_eq!(
+        super::evaluate(&storage, &variable("day")),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+
+    let whileNotFull = Content::While(
+        binary(BiOperation::Less, variable("hp"), value(100)),
+        None,
+        vec![
+            command(Command::Add("hp".to_string(), value(30))),
+            text("+"),
+        ],
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("hp".to_string(), value(0))),
+                whileNotFull.clone(),
+                command(Command::Write(variable("hp"), Format::Plain)),
+            ]
+        ),
+        Ok("++++120".to_string())
+    );
+
+    // Loops which would run too long fail instead of hanging.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Config(Setting::Iterations(50))),
+                Content::While(value(1), None, vec![]),
+            ]
+        ),
+        Err(RunErr::IterationLimit(50))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![Content::Repeat(value(51), None, vec![text("x")])]
+        ),
+        Err(RunErr::IterationLimit(50))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                Content::Repeat(value(50), None, vec![]),
+                Content::Repeat(value(-3), None, vec![text("x")]),
+            ]
+        ),
+        Ok(String::new())
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![Content::Repeat(
+                Expression::Value(Number::parse("2.5").expect("")),
+                None,
+                vec![]
+            )]
+        ),
+        Err(RunErr::InvalidRepeatCount("2.5".to_string()))
+    );
+
+    // The index is there for the first check already, and it's gone after the loop, even one which fails.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                Content::While(
+                    binary(BiOperation::Less, variable("i"), value(3)),
+                    Some("i".to_string()),
+                    vec![command(Command::Write(variable("i"), Format::Plain))],
+                ),
+                Content::While(value(1), Some("i".to_string()), vec![]),
+            ]
+        ),
+        Err(RunErr::IterationLimit(50))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("i")),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+
+    // A loop can't take the name of a variable which is already there, even if it never runs.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("i".to_string(), value(5))),
+                Content::Repeat(value(0), Some("i".to_string()), vec![text("x")]),
+            ]
+        ),
+        Err(RunErr::TriedToInitializeExistingVariable)
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("i")),
+        Ok(Value::from(5))
+    );
+}
+
+#[test]
+fn functions() {
+    let mut storage = Storage::new();
+    storage
+        .createVariable("hp".to_string(), Value::Number(Number::from(95)))
+        .expect("");
+    storage
+        .createVariable("max_hp".to_string(), Value::Number(Number::from(120)))
+        .expect("");
+
+    let call =
+        |name: &str, arguments: Vec<Expression>| Expression::Call(name.to_string(), arguments);
+    let evaluate = |expression: &Expression| super::evaluate(&storage, expression);
+
+    let healed = Expression::Binary(
+        BiOperation::Add,
+        Box::new(variable("hp")),
+        Box::new(value(40)),
+    );
+    assert_eq!(
+        evaluate(&call("clamp", vec![healed, value(0), variable("max_hp")])),
+        Ok(Value::Number(Number::from(120)))
+    );
+    assert_eq!(
+        evaluate(&call(
+            "clamp",
+            vec![value(-5), value(0), variable("max_hp")]
+        )),
+        Ok(Value::Number(Number::from(0)))
+    );
+    assert_eq!(
+        evaluate(&call("clamp", vec![value(5), value(10), value(0)])),
+        Err(RunErr::InvalidBounds("clamp(5, 10, 0)".to_string()))
+    );
+    assert_eq!(
+        evaluate(&call("min", vec![value(3), value(-2), variable("hp")])),
+        Ok(Value::Number(Number::from(-2)))
+    );
+    assert_eq!(
+        evaluate(&call(
+            "max",
+            vec![value(3), Expression::Value(Number::parse("3.5").expect(""))]
+        )),
+        Ok(Value::Number(Number::parse("3.5").expect("")))
+    );
+    assert_eq!(
+        evaluate(&call("abs", vec![value(-7)])),
+        Ok(Value::Number(Number::from(7)))
+    );
+    assert_eq!(
+        evaluate(&call("sign", vec![value(-7)])),
+        Ok(Value::Number(Number::from(-1)))
+    );
+    assert_eq!(
+        evaluate(&call("sign", vec![value(0)])),
+        Ok(Value::Number(Number::from(0)))
+    );
+    assert_eq!(
+        evaluate(&call("pow", vec![value(2), value(10)])),
+        Ok(Value::Number(Number::from(1024)))
+    );
+    assert_eq!(
+        evaluate(&call("pow", vec![value(2), value(-1)])),
+        Err(RunErr::NegativeExponent)
+    );
+
+    // `floor_div` rounds down even when the author chose a different rounding.
+    storage.applySetting(Setting::Rounding(Rounding::Truncate));
+    let evaluate = |expression: &Expression| super::evaluate(&storage, expression);
+    assert_eq!(
+        evaluate(&call("floor_div", vec![value(-7), value(2)])),
+        Ok(Value::Number(Number::from(-4)))
+    );
+    assert_eq!(
+        evaluate(&call("floor_div", vec![value(7), value(0)])),
+        Err(RunErr::DivisionByZero("floor_div(7, 0)".to_string()))
+    );
+
+    assert_eq!(
+        evaluate(&call("abs", vec![Expression::Value(Number::Int(i64::MIN))])),
+        Err(RunErr::ArithmeticOverflow(
+            "abs((-9223372036854775808))".to_string(),
+            String::new()
+        ))
+    );
+    assert_eq!(
+        evaluate(&call("clamp", vec![value(1), value(2)])),
+        Err(RunErr::WrongArity(
+            "clamp".to_string(),
+            "3 arguments".to_string()
+        ))
+    );
+    assert_eq!(
+        evaluate(&call("max", vec![])),
+        Err(RunErr::WrongArity(
+            "max".to_string(),
+            "at least 1 argument".to_string()
+        ))
+    );
+    assert_eq!(
+        evaluate(&call("heal", vec![value(1)])),
+        Err(RunErr::UnknownFunction("heal".to_string()))
+    );
+}
+
+#[test]
+fn strings() {
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("class".to_string(), string("Spellblade"))),
+                command(Command::Let("level".to_string(), value(12))),
+                command(Command::Let("title".to_string(), string("Hero"))),
+                command(Command::Add("title".to_string(), string(" the Brave"))),
+                command(Command::Write(variable("class"), Format::Plain)),
+                Content::Text(", ".to_string()),
+                command(Command::Write(variable("title"), Format::Plain)),
+                Content::Text(", ".to_string()),
+                command(Command::Write(
+                    binary(BiOperation::Add, string("Level "), variable("level")),
+                    Format::Plain
+                )),
+                Content::Conditional(
+                    vec![(
+                        binary(BiOperation::Equal, variable("class"), string("Spellblade")),
+                        vec![Content::Text("!".to_string())]
+                    )],
+                    vec![]
+                ),
+            ]
+        ),
+        Ok("Spellblade, Hero the Brave, Level 12!".to_string())
+    );
+
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::NotEqual, variable("class"), string("Mage"))
+        ),
+        Ok(Value::from(1))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("class")),
+        Ok(Value::from("Spellblade"))
+    );
+
+    // Mixing the types in anything but joining them is an error which says what went wrong.
+    let mismatch = |expression: &str, found: &str, expected: &str| {
+        RunErr::TypeMismatch(
+            expression.to_string(),
+            found.to_string(),
+            expected.to_string(),
+        )
+    };
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::Multiply, variable("class"), value(2))
+        ),
+        Err(mismatch("class", "a string", "a number"))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::Less, variable("level"), variable("class"))
+        ),
+        Err(mismatch("class", "a string", "a number"))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &binary(BiOperation::Equal, variable("class"), value(2))
+        ),
+        Err(mismatch("2", "a number", "a string"))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &Expression::Unary(UnOperation::Minus, Box::new(variable("title")))
+        ),
+        Err(mismatch("title", "a string", "a number"))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Subtract("title".to_string(), value(1)))]
+        ),
+        Err(mismatch("title", "a string", "a number"))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Add("level".to_string(), string("1")))]
+        ),
+        Err(mismatch("\"1\"", "a string", "a number"))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![Content::Conditional(
+                vec![(variable("class"), vec![])],
+                vec![]
+            )]
+        ),
+        Err(mismatch("class", "a string", "a number"))
+    );
+
+    // Setting a variable to a value of another type fails, and the variable keeps its old value.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("hp".to_string(), value(10))),
+                command(Command::Set("hp".to_string(), string("dead"))),
+            ]
+        ),
+        Err(mismatch("\"dead\"", "a string", "a number"))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::List("inventory".to_string())),
+                command(Command::Set("inventory".to_string(), value(5))),
+            ]
+        ),
+        Err(mismatch("5", "a number", "a list"))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Set("title".to_string(), variable("hp")))]
+        ),
+        Err(mismatch("hp", "a number", "a string"))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("hp")),
+        Ok(Value::from(10))
+    );
+}
+
+#[test]
+fn entities() {
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.vit".to_string(), value(12))),
+                command(Command::Let("hero.str".to_string(), value(10))),
+                command(Command::Derive(
+                    "hero.hp".to_string(),
+                    binary(BiOperation::Multiply, variable("hero.vit"), value(10))
+                )),
+                command(Command::Add("hero.str".to_string(), value(2))),
+                command(Command::Entity("hero.pet".to_string())),
+                command(Command::Let("hero.pet.hp".to_string(), value(30))),
+                command(Command::Fields("hero".to_string())),
+                Content::Text(" | ".to_string()),
+                command(Command::Write(
+                    binary(
+                        BiOperation::Add,
+                        variable("hero.hp"),
+                        variable("hero.pet.hp")
+                    ),
+                    Format::Plain
+                )),
+            ]
+        ),
+        // The fields are written in the order they were created in, and the pet isn't one of them.
+        Ok("vit: 12, str: 12, hp: 120 | 150".to_string())
+    );
+
+    // Entities and variables share the names.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Let("hero".to_string(), value(1)))]
+        ),
+        Err(RunErr::TriedToInitializeExistingVariable)
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Entity("hero.str".to_string()))]
+        ),
+        Err(RunErr::TriedToInitializeExistingVariable)
+    );
+
+    // Fields can't be created for entities which don't exist.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Let("goblin.str".to_string(), value(3)))]
+        ),
+        Err(RunErr::NonexistentEntity("goblin".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Entity("goblin.pet".to_string()))]
+        ),
+        Err(RunErr::NonexistentEntity("goblin".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Fields("goblin".to_string()))]
+        ),
+        Err(RunErr::NonexistentEntity("goblin".to_string()))
+    );
+
+    // Deleting an entity removes everything that's a part of it, so it can be created again from scratch.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Delete("hero".to_string())),
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(5))),
+                command(Command::Fields("hero".to_string())),
+            ]
+        ),
+        Ok("str: 5".to_string())
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("hero.pet.hp")),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Delete("hero.pet".to_string()))]
+        ),
+        Err(RunErr::NonexistentEntity("hero.pet".to_string()))
+    );
+}
+
+#[test]
+fn classes() {
+    let field = |name: &str, formula: Expression| (name.to_string(), formula);
+    // `base + level * growth`
+    let growth = |base: i64, growth: i64| {
+        binary(
+            BiOperation::Add,
+            value(base),
+            binary(BiOperation::Multiply, variable("level"), value(growth)),
+        )
+    };
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("bonus".to_string(), value(100))),
+                command(Command::Class(
+                    "Warrior".to_string(),
+                    vec![
+                        field("level", value(1)),
+                        field("str", growth(10, 2)),
+                        field("vit", growth(8, 3)),
+                    ]
+                )),
+                // The overrides can use the other fields and ordinary variables alike.
+                command(Command::New(
+                    "hero".to_string(),
+                    "Warrior".to_string(),
+                    vec![
+                        field("level", value(5)),
+                        field(
+                            "vit",
+                            binary(BiOperation::Add, variable("str"), variable("bonus"))
+                        ),
+                        field("luck", value(7)),
+                    ]
+                )),
+                command(Command::New(
+                    "guard".to_string(),
+                    "Warrior".to_string(),
+                    vec![]
+                )),
+                command(Command::Fields("hero".to_string())),
+                Content::Text(" | ".to_string()),
+                command(Command::Fields("guard".to_string())),
+                Content::Text(" | ".to_string()),
+                // The fields follow the level of their own entity.
+                command(Command::Add("hero.level".to_string(), value(1))),
+                command(Command::Fields("hero".to_string())),
+                Content::Text(" | ".to_string()),
+                command(Command::Fields("guard".to_string())),
+            ]
+        ),
+        Ok(
+            "level: 5, str: 20, vit: 120, luck: 7 | level: 1, str: 12, vit: 11 | \
+            level: 6, str: 22, vit: 122, luck: 7 | level: 1, str: 12, vit: 11"
+                .to_string()
+        )
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Class("Warrior".to_string(), vec![]))]
+        ),
+        Err(RunErr::TriedToRedefineClass("Warrior".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::New(
+                "mage".to_string(),
+                "Mage".to_string(),
+                vec![]
+            ))]
+        ),
+        Err(RunErr::NonexistentClass("Mage".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::New(
+                "hero".to_string(),
+                "Warrior".to_string(),
+                vec![]
+            ))]
+        ),
+        Err(RunErr::TriedToInitializeExistingVariable)
+    );
+
+    // A field which can't be created leaves no trace of the entity.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::New(
+                "golem".to_string(),
+                "Warrior".to_string(),
+                vec![
+                    field("level", variable("str")),
+                    field("str", variable("level"))
+                ]
+            ))]
+        ),
+        Err(RunErr::CyclicDependency("golem.str".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Fields("golem".to_string()))]
+        ),
+        Err(RunErr::NonexistentEntity("golem".to_string()))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("golem.level")),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+}
+
+#[test]
+fn lists() {
+    let call =
+        |name: &str, arguments: Vec<Expression>| Expression::Call(name.to_string(), arguments);
+    let push = |item: &str, quantity: i64| {
+        command(Command::Push(
+            "inventory".to_string(),
+            string(item),
+            value(quantity),
+        ))
+    };
+    let remove = |item: &str, quantity: i64| {
+        command(Command::Remove(
+            "inventory".to_string(),
+            string(item),
+            value(quantity),
+        ))
+    };
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::List("inventory".to_string())),
+                push("Health Potion", 2),
+                push("Iron Sword", 1),
+                push("Health Potion", 3),
+                push("Torch", 4),
+                remove("Health Potion", 2),
+                remove("Torch", 4),
+                command(Command::Write(variable("inventory"), Format::Plain)),
+                Content::Text("\n".to_string()),
+                command(Command::Write(variable("inventory"), Format::Bullets)),
+            ]
+        ),
+        // An item is gone once there are none of it left.
+        Ok("Health Potion x3, Iron Sword\n- Health Potion x3\n- Iron Sword".to_string())
+    );
+
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &call(
+                "count",
+                vec![variable("inventory"), string("Health Potion")]
+            )
+        ),
+        Ok(Value::from(3))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &call("count", vec![variable("inventory")])),
+        Ok(Value::from(4))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &call("count", vec![variable("inventory"), string("Torch")])
+        ),
+        Ok(Value::from(0))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &call(
+                "contains",
+                vec![
+                    variable("inventory"),
+                    string("Iron Sword"),
+                    string("Health Potion")
+                ]
+            )
+        ),
+        Ok(Value::from(1))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &call("contains", vec![variable("inventory"), string("Torch")])
+        ),
+        Ok(Value::from(0))
+    );
+
+    // Taking out more than there is fails, and leaves the list as it was.
+    assert_eq!(
+        super::run(&mut storage, vec![remove("Health Potion", 4)]),
+        Err(RunErr::MissingItem(
+            "inventory".to_string(),
+            "Health Potion x4".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![remove("Torch", 1)]),
+        Err(RunErr::MissingItem(
+            "inventory".to_string(),
+            "Torch".to_string()
+        ))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("inventory")).map(|list| list.to_string()),
+        Ok("Health Potion x3, Iron Sword".to_string())
+    );
+
+    assert_eq!(
+        super::run(&mut storage, vec![push("Torch", 0)]),
+        Err(RunErr::InvalidQuantity("0".to_string()))
+    );
+    let mismatch = |expression: &str, found: &str, expected: &str| {
+        RunErr::TypeMismatch(
+            expression.to_string(),
+            found.to_string(),
+            expected.to_string(),
+        )
+    };
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Push(
+                "inventory".to_string(),
+                value(5),
+                value(1)
+            ))]
+        ),
+        Err(mismatch("5", "a number", "a string"))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &call("count", vec![value(5)])),
+        Err(mismatch("5", "a number", "a list"))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &call("contains", vec![variable("inventory")])),
+        Err(RunErr::WrongArity(
+            "contains".to_string(),
+            "at least 2 arguments".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Add("inventory".to_string(), value(1)))]
+        ),
+        Err(mismatch("inventory", "a list", "a number"))
+    );
+}
+
+#[test]
+fn macros() {
+    let call = |name: &str, arguments: Vec<Expression>| {
+        command(Command::Call(name.to_string(), arguments))
+    };
+    let define = |name: &str, parameters: &[&str], contents: Vec<Content>| {
+        Content::Macro(
+            name.to_string(),
+            parameters
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .collect(),
+            contents,
+        )
+    };
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                define(
+                    "levelup",
+                    &["who", "amount"],
+                    vec![
+                        command(Command::Add("who.level".to_string(), variable("amount"))),
+                        Content::Text("Level ".to_string()),
+                        command(Command::Write(variable("who.level"), Format::Plain)),
+                        Content::Text("! ".to_string()),
+                    ]
+                ),
+                // A macro can call itself, as long as it stops at some point.
+                define(
+                    "countdown",
+                    &["n"],
+                    vec![Content::Conditional(
+                        vec![(
+                            binary(BiOperation::Greater, variable("n"), value(0)),
+                            vec![
+                                command(Command::Write(variable("n"), Format::Plain)),
+                                Content::Text(" ".to_string()),
+                                call(
+                                    "countdown",
+                                    vec![binary(BiOperation::Subtract, variable("n"), value(1))]
+                                ),
+                            ]
+                        )],
+                        vec![Content::Text("Go!".to_string())]
+                    )]
+                ),
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.level".to_string(), value(1))),
+                command(Command::Let("bonus".to_string(), value(2))),
+                call("levelup", vec![variable("hero"), value(1)]),
+                call(
+                    "levelup",
+                    vec![
+                        variable("hero"),
+                        binary(BiOperation::Add, variable("bonus"), value(1))
+                    ]
+                ),
+                call("countdown", vec![value(3)]),
+            ]
+        ),
+        Ok("Level 2! Level 5! 3 2 1 Go!".to_string())
+    );
+
+    // A macro which never stops calling itself is stopped at the limit.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                define("forever", &[], vec![call("forever", vec![])]),
+                command(Command::Config(Setting::Depth(20))),
+                call("forever", vec![]),
+            ]
+        ),
+        Err(RunErr::RecursionLimit(20))
+    );
+    // The calls which failed don't count towards the limit anymore.
+    assert_eq!(
+        super::run(&mut storage, vec![call("countdown", vec![value(19)])])
+            .map(|output| output.ends_with("1 Go!")),
+        Ok(true)
+    );
+
+    assert_eq!(
+        super::run(&mut storage, vec![call("levelup", vec![variable("hero")])]),
+        Err(RunErr::WrongArity(
+            "levelup".to_string(),
+            "2 arguments".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![call("recap", vec![])]),
+        Err(RunErr::UnknownMacro("recap".to_string()))
+    );
+    // The entity has to be a name, as the macro adds to one of its fields.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![call("levelup", vec![value(1), value(1)])]
+        ),
+        Err(RunErr::NotAVariable("1".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Def("recap".to_string(), vec![]))]
+        ),
+        Err(RunErr::UnexpectedBlockMarker("def".to_string()))
+    );
+
+    // Parameters can stand for the names of items, skills, slots, effects and modifier sources as well.
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Item(
+                    "Iron Sword".to_string(),
+                    "weapon".to_string(),
+                    vec![("str".to_string(), value(5))]
+                )),
+                command(Command::Skill("Spark".to_string(), value(2), vec![])),
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(10))),
+                define(
+                    "gear",
+                    &["who", "it", "skill", "source"],
+                    vec![
+                        command(Command::Equip("who".to_string(), "it".to_string())),
+                        command(Command::Learn("who".to_string(), "skill".to_string())),
+                        command(Command::Rankup("who".to_string(), "skill".to_string())),
+                        command(Command::Effect(
+                            "source".to_string(),
+                            "who.str".to_string(),
+                            value(1),
+                            value(2),
+                            TimeUnit::Ticks
+                        )),
+                        command(Command::Modifier(
+                            "who.str".to_string(),
+                            "source".to_string(),
+                            value(2),
+                            false
+                        )),
+                    ]
+                ),
+                call(
+                    "gear",
+                    vec![
+                        variable("hero"),
+                        string("Iron Sword"),
+                        variable("Spark"),
+                        string("Blessing of Dawn")
+                    ]
+                ),
+                command(Command::Explain("hero.str".to_string())),
+                Content::Text(" | ".to_string()),
+                command(Command::Skills("hero".to_string())),
+            ]
+        ),
+        Ok("hero.str 18 = 10 base + 5 [Iron Sword] + 1 [Blessing of Dawn] + 2 [Blessing of Dawn] | Spark 2/2".to_string())
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![call(
+                "gear",
+                vec![variable("hero"), value(5), variable("Spark"), string("x")]
+            )]
+        ),
+        Err(RunErr::NotAName("5".to_string()))
+    );
+}
+
+#[test]
+fn rolls() {
+    let random =
+        |low: Expression, high: Expression| Expression::Call("random".to_string(), vec![low, high]);
+
+    let chapter = || {
+        vec![
+            command(Command::Seed(1234)),
+            command(Command::Roll(binary(
+                BiOperation::Add,
+                Expression::Dice(2, 6),
+                value(3),
+            ))),
+            Content::Text(" ".to_string()),
+            command(Command::Write(random(value(1), value(100)), Format::Plain)),
+        ]
+    };
+
+    // Rendering the chapter again gives the same rolls, even with the same storage.
+    let mut storage = Storage::new();
+    let first = super::run(&mut storage, chapter()).expect("The rolls should work");
+    assert_eq!(super::run(&mut storage, chapter()), Ok(first.clone()));
+    assert_eq!(
+        super::run(&mut Storage::new(), chapter()),
+        Ok(first.clone())
+    );
+
+    // Everything that was rolled is in the log, with one entry for every roll, and adds up to what was written.
+    let rolls = storage.rng().rolls();
+    assert_eq!(rolls.len(), 4);
+    assert_eq!(rolls[0].what, "(2d6 + 3)");
+    assert_eq!(rolls[1].what, "random(1, 100)");
+    assert_eq!(rolls[..2], rolls[2..]);
+
+    let dice = rolls[0]
+        .results
+        .iter()
+        .map(|die| die.toWhole().expect("Dice are whole"))
+        .collect::<Vec<i64>>();
+    assert_eq!(dice.len(), 2);
+    assert!(dice.iter().all(|die| (1..=6).contains(die)));
+    assert_eq!(
+        rolls[0].total,
+        Some(Number::from(dice.iter().sum::<i64>() + 3))
+    );
+    assert_eq!(rolls[1].total, None);
+    assert_eq!(
+        first,
+        format!("{} {}", dice.iter().sum::<i64>() + 3, rolls[1].results[0])
+    );
+
+    // An argument of a macro is rolled once, when the macro is called, however many times it's used in it.
+    let mut storage = Storage::new();
+    let output = super::run(
+        &mut storage,
+        vec![
+            command(Command::Let("hp".to_string(), value(0))),
+            Content::Macro(
+                "heal".to_string(),
+                vec!["amount".to_string()],
+                vec![
+                    command(Command::Add(
+                        "hp".to_string(),
+                        Expression::Variable("amount".to_string()),
+                    )),
+                    command(Command::Write(
+                        Expression::Variable("amount".to_string()),
+                        Format::Plain,
+                    )),
+                ],
+            ),
+            command(Command::Call(
+                "heal".to_string(),
+                vec![Expression::Dice(1, 20)],
+            )),
+            Content::Text(" ".to_string()),
+            command(Command::Write(
+                Expression::Variable("hp".to_string()),
+                Format::Plain,
+            )),
+        ],
+    )
+    .expect("The macro should work");
+    let rolls = storage.rng().rolls();
+    assert_eq!(rolls.len(), 1);
+    assert_eq!(output, format!("{0} {0}", rolls[0].results[0]));
+
+    // A derived variable would roll again on every read, so it can't roll at all.
+    let mut storage = Storage::new();
+    for expression in [
+        binary(BiOperation::Add, Expression::Dice(1, 6), value(2)),
+        random(value(1), Expression::Variable("level".to_string())),
+    ] {
+        assert_eq!(
+            super::run(
+                &mut storage,
+                vec![command(Command::Derive("damage".to_string(), expression))]
+            ),
+            Err(RunErr::RandomDerived("damage".to_string()))
+        );
+    }
+    assert!(storage.rng().rolls().is_empty());
+
+    assert_eq!(
+        super::evaluate(&storage, &random(value(5), value(5))),
+        Ok(Value::from(5))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &random(value(6), value(1))),
+        Err(RunErr::InvalidRange("random(6, 1)".to_string()))
+    );
+    assert_eq!(
+        super::evaluate(
+            &storage,
+            &random(value(1), Expression::Value(Number::parse("2.5").expect("")))
+        ),
+        Err(RunErr::InvalidRange("random(1, 2.5)".to_string()))
+    );
+}
+
+#[test]
+fn pools() {
+    let space = || Content::Text(" ".to_string());
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Pool("hp".to_string(), value(120))),
+                write(variable("hp")),
+                space(),
+                // It never goes below zero or over the maximum.
+                command(Command::Subtract("hp".to_string(), value(200))),
+                write(variable("hp")),
+                space(),
+                command(Command::Add("hp".to_string(), value(73))),
+                write(variable("hp")),
+                space(),
+                command(Command::Add("hp".to_string(), value(1000))),
+                write(variable("hp")),
+                space(),
+                command(Command::Set("hp".to_string(), value(73))),
+                write(variable("hp")),
+                space(),
+                // In calculations, it's the current value.
+                write(binary(BiOperation::Multiply, variable("hp"), value(2))),
+                space(),
+                write(variable("hp.max")),
+                space(),
+                // Divided like any other whole numbers, so rounded down.
+                write(variable("hp.percent")),
+                space(),
+                write(binary(
+                    BiOperation::Add,
+                    Expression::Str("HP: ".to_string()),
+                    variable("hp")
+                )),
+            ]
+        ),
+        Ok("120/120 0/120 73/120 120/120 73/120 146 120 60 HP: 73/120".to_string())
+    );
+
+    // Raising the maximum keeps the current value, unless the pool is set to refill.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Set("hp.max".to_string(), value(146))),
+                write(variable("hp")),
+                space(),
+                command(Command::Subtract("hp.max".to_string(), value(100))),
+                write(variable("hp")),
+                space(),
+                command(Command::Config(Setting::Refill(Refill::Proportional))),
+                command(Command::Add("hp.max".to_string(), value(46))),
+                write(variable("hp")),
+                space(),
+                command(Command::Set("hp".to_string(), value(23))),
+                command(Command::Subtract("hp.max".to_string(), value(46))),
+                write(variable("hp")),
+            ]
+        ),
+        Ok("73/146 46/46 92/92 11/46".to_string())
+    );
+
+    // Pools can be fields of entities too. The refill is still proportional, so 25 of 30 becomes 33 of 40.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Pool("hero.mp".to_string(), value(30))),
+                command(Command::Subtract("hero.mp".to_string(), value(5))),
+                command(Command::Add("hero.mp.max".to_string(), value(10))),
+                command(Command::Fields("hero".to_string())),
+            ]
+        ),
+        Ok("mp: 33/40".to_string())
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Subtract("hp.max".to_string(), value(46)))]
+        ),
+        Err(RunErr::InvalidMaximum("hp".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Pool("sp".to_string(), value(0)))]
+        ),
+        Err(RunErr::InvalidMaximum("sp".to_string()))
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("hp.min")),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::evaluate(&storage, &variable("hp")),
+        Ok(Value::Pool(
+            crate::types::value::Pool::new(Number::from(46)).withCurrent(Number::from(11))
+        ))
+    );
+}
+
+#[test]
+fn effects() {
+    let space = || Content::Text(" ".to_string());
+    let effect = |name: &str, target: &str, amount: i64, duration: i64, unit: TimeUnit| {
+        command(Command::Effect(
+            name.to_string(),
+            target.to_string(),
+            value(amount),
+            value(duration),
+            unit,
+        ))
+    };
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(10))),
+                command(Command::Pool("hp".to_string(), value(100))),
+                effect("Blessing of Dawn", "hero.str", 5, 3, TimeUnit::Ticks),
+                effect("Curse", "hp.max", -20, 1, TimeUnit::Chapters),
+                write(variable("hero.str")),
+                space(),
+                write(variable("hp")),
+                space(),
+                command(Command::Effects(Some("hero".to_string()))),
+                Content::Text(" | ".to_string()),
+                command(Command::Effects(None)),
+                space(),
+                // Whatever happened to the variable in the meantime is kept once the effect runs out.
+                command(Command::Add("hero.str".to_string(), value(2))),
+                command(Command::Tick(value(2))),
+                command(Command::Effects(None)),
+                space(),
+                command(Command::Tick(value(1))),
+                write(variable("hero.str")),
+                space(),
+                command(Command::Chapter),
+                write(variable("hp")),
+                command(Command::Effects(None)),
+            ]
+        ),
+        Ok("15 80/80 Blessing of Dawn (hero.str +5, 3 ticks left) | Blessing of Dawn (hero.str +5, 3 ticks left), Curse (hp.max -20, 1 chapter left) Blessing of Dawn (hero.str +5, 1 tick left), Curse (hp.max -20, 1 chapter left) 12 80/100".to_string())
+    );
+
+    // An effect can only change a variable which is there. Deleting an entity takes its effects with it.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                effect("Haste", "hero.agi", 3, 2, TimeUnit::Ticks),
+                command(Command::Delete("hero".to_string())),
+                command(Command::Effects(None)),
+            ]
+        ),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("hero.agi".to_string(), value(7))),
+                effect("Haste", "hero.agi", 3, 2, TimeUnit::Ticks),
+                command(Command::Delete("hero".to_string())),
+                command(Command::Effects(None)),
+                command(Command::Tick(value(5))),
+            ]
+        ),
+        Ok("".to_string())
+    );
+
+    // On a number, the effect is a modifier, so it shows up in the breakdown of the stat. On a pool, only the
+    // change which was actually made is taken back, so a full pool doesn't lose anything.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("str".to_string(), value(20))),
+                command(Command::Modifier(
+                    "str".to_string(),
+                    "class".to_string(),
+                    value(6),
+                    false
+                )),
+                effect("Blessing of Dawn", "str", 5, 3, TimeUnit::Ticks),
+                command(Command::Explain("str".to_string())),
+                space(),
+                command(Command::Set("hp".to_string(), value(100))),
+                effect("Blessing of Dawn", "hp", 20, 1, TimeUnit::Ticks),
+                write(variable("hp")),
+                space(),
+                command(Command::Tick(value(1))),
+                write(variable("hp")),
+                space(),
+                command(Command::Subtract("hp".to_string(), value(90))),
+                effect("Blessing of Dawn", "hp", 20, 1, TimeUnit::Ticks),
+                write(variable("hp")),
+                space(),
+                command(Command::Tick(value(1))),
+                write(variable("hp")),
+                space(),
+                // Starting an effect again renews it, instead of stacking it.
+                effect("Blessing of Dawn", "str", 5, 3, TimeUnit::Ticks),
+                command(Command::Explain("str".to_string())),
+                space(),
+                command(Command::Tick(value(3))),
+                command(Command::Explain("str".to_string())),
+            ]
+        ),
+        Ok("str 31 = 20 base + 6 class + 5 [Blessing of Dawn] 100/100 100/100 30/100 10/100 str 31 = 20 base + 6 class + 5 [Blessing of Dawn] str 26 = 20 base + 6 class".to_string())
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let(
+                    "title".to_string(),
+                    Expression::Str("Sir".to_string())
+                )),
+                effect("Blessing of Dawn", "title", 5, 3, TimeUnit::Ticks),
+            ]
+        ),
+        Err(RunErr::TypeMismatch(
+            "title".to_string(),
+            "a string".to_string(),
+            "a number".to_string()
+        ))
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![effect("Haste", "hp", 3, 0, TimeUnit::Ticks)]
+        ),
+        Err(RunErr::InvalidDuration("0".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Tick(Expression::Value(
+                Number::parse("1.5").expect("Not a number")
+            )))]
+        ),
+        Err(RunErr::InvalidDuration("1.5".to_string()))
+    );
+}
+
+#[test]
+fn curves() {
+    let curve = |experience: &str, level: &str, thresholds: Thresholds, hook: Option<&str>| {
+        command(Command::Curve(
+            experience.to_string(),
+            Curve {
+                level: level.to_string(),
+                thresholds,
+                hook: hook.map(|hook| (hook.to_string(), vec![variable("hero")])),
+            },
+        ))
+    };
+    // 100 experience for the second level, 400 for the third and 900 for the fourth.
+    let squared = Thresholds::Formula(binary(
+        BiOperation::Multiply,
+        value(100),
+        binary(
+            BiOperation::Exponentiate,
+            binary(BiOperation::Subtract, variable("hero.level"), value(1)),
+            value(2),
+        ),
+    ));
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                Content::Macro(
+                    "levelup".to_string(),
+                    vec!["who".to_string()],
+                    vec![
+                        command(Command::Add("who.str".to_string(), value(2))),
+                        Content::Text("[Level Up!] ".to_string()),
+                    ],
+                ),
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.level".to_string(), value(1))),
+                command(Command::Let("hero.str".to_string(), value(10))),
+                command(Command::Let("hero.xp".to_string(), value(0))),
+                curve("hero.xp", "hero.level", squared.clone(), Some("levelup")),
+                // Two levels at once.
+                command(Command::Add("hero.xp".to_string(), value(500))),
+                write(variable("hero.level")),
+                Content::Text(" ".to_string()),
+                write(variable("hero.str")),
+                Content::Text(" ".to_string()),
+                // Not enough for the fourth level, and losing experience doesn't take levels away.
+                command(Command::Add("hero.xp".to_string(), value(399))),
+                command(Command::Subtract("hero.xp".to_string(), value(899))),
+                write(variable("hero.level")),
+            ]
+        ),
+        Ok("[Level Up!] [Level Up!] 3 14 3".to_string())
+    );
+
+    // There are no levels past the end of the table.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                curve(
+                    "hero.xp",
+                    "hero.level",
+                    Thresholds::Table(vec![value(0), value(100), value(200), value(300)]),
+                    None
+                ),
+                command(Command::Set("hero.xp".to_string(), value(10000))),
+                write(variable("hero.level")),
+            ]
+        ),
+        Ok("4".to_string())
+    );
+
+    // A curve which doesn't grow would level up forever.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("xp".to_string(), value(0))),
+                command(Command::Let("level".to_string(), value(1))),
+                curve("xp", "level", Thresholds::Formula(value(0)), None),
+                command(Command::Add("xp".to_string(), value(1))),
+            ]
+        ),
+        Err(RunErr::EndlessCurve("xp".to_string(), 10000))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let(
+                    "rank".to_string(),
+                    Expression::Value(Number::parse("1.5").expect("Not a number"))
+                )),
+                command(Command::Let("fame".to_string(), value(0))),
+                curve("fame", "rank", Thresholds::Table(vec![value(0)]), None),
+                command(Command::Add("fame".to_string(), value(1))),
+            ]
+        ),
+        Err(RunErr::InvalidLevel("2.5".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("renown".to_string(), value(0))),
+                curve("renown", "level", squared, Some("unknown")),
+                command(Command::Add("renown".to_string(), value(1000))),
+            ]
+        ),
+        Err(RunErr::UnknownMacro("unknown".to_string()))
+    );
+}
+
+#[test]
+fn modifiers() {
+    let space = || Content::Text(" | ".to_string());
+    let modifier = |stat: &str, source: &str, amount: i64, percent: bool| {
+        command(Command::Modifier(
+            stat.to_string(),
+            source.to_string(),
+            value(amount),
+            percent,
+        ))
+    };
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(20))),
+                command(Command::Let("hero.vit".to_string(), value(10))),
+                modifier("hero.str", "class", 6, false),
+                modifier("hero.str", "Ring of Might", 8, false),
+                modifier("hero.vit", "Ring of Might", 2, false),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                // The base can still be changed, and the modifiers stay on top of it.
+                command(Command::Add("hero.str".to_string(), value(10))),
+                write(variable("hero.str")),
+                space(),
+                // The percentages are summed up and applied after the flat modifiers.
+                modifier("hero.str", "haste", 10, true),
+                modifier("hero.str", "Blessing of Dawn", 10, true),
+                modifier("hero.str", "curse", -4, false),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                // Taking the ring off takes away everything it gave, and a source replaces its old modifier.
+                command(Command::Revoke("Ring of Might".to_string())),
+                modifier("hero.str", "curse", -10, false),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                write(variable("hero.vit")),
+            ]
+        ),
+        Ok("hero.str 34 = 20 base + 6 class + 8 [Ring of Might] | 44 | hero.str 48 = 30 base + 6 class + 8 [Ring of Might] + 10% haste + 10% [Blessing of Dawn] - 4 curse | hero.str 31.2 = 30 base + 6 class + 10% haste + 10% [Blessing of Dawn] - 10 curse | 10".to_string())
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Revoke("Ring of Might".to_string()))]
+        ),
+        Err(RunErr::NonexistentModifier("Ring of Might".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![modifier("hero.agi", "class", 2, false)]),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let(
+                    "title".to_string(),
+                    Expression::Str("Sir".to_string())
+                )),
+                modifier("title", "class", 2, false),
+            ]
+        ),
+        Err(RunErr::TypeMismatch(
+            "title".to_string(),
+            "a string".to_string(),
+            "a number".to_string()
+        ))
+    );
+}
+
+#[test]
+fn equipment() {
+    let space = || Content::Text(" | ".to_string());
+    let item = |item: &str, slot: &str, bonuses: &[(&str, i64)]| {
+        command(Command::Item(
+            item.to_string(),
+            slot.to_string(),
+            bonuses
+                .iter()
+                .map(|(stat, amount)| (stat.to_string(), value(*amount)))
+                .collect(),
+        ))
+    };
+    let equip =
+        |entity: &str, item: &str| command(Command::Equip(entity.to_string(), item.to_string()));
+    let unequip =
+        |entity: &str, slot: &str| command(Command::Unequip(entity.to_string(), slot.to_string()));
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                item("Iron Sword", "weapon", &[("str", 5), ("agi", 2)]),
+                item("Ring of Might", "ring", &[("str", 8)]),
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(20))),
+                command(Command::Let("hero.agi".to_string(), value(10))),
+                command(Command::Entity("rival".to_string())),
+                command(Command::Let("rival.str".to_string(), value(15))),
+                command(Command::Let("rival.agi".to_string(), value(15))),
+                equip("hero", "Iron Sword"),
+                equip("hero", "Ring of Might"),
+                equip("rival", "Iron Sword"),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                write(variable("hero.agi")),
+                space(),
+                // Taking the sword off the hero leaves the rival's alone.
+                unequip("hero", "weapon"),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                write(variable("hero.agi")),
+                space(),
+                write(variable("rival.str")),
+            ]
+        ),
+        Ok("hero.str 33 = 20 base + 5 [Iron Sword] + 8 [Ring of Might] | 12 | hero.str 28 = 20 base + 8 [Ring of Might] | 10 | 20".to_string())
+    );
+
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                item("Steel Sword", "weapon", &[("str", 7)]),
+                equip("rival", "Steel Sword")
+            ]
+        ),
+        Err(RunErr::SlotTaken(
+            "rival.weapon".to_string(),
+            "Iron Sword".to_string()
+        ))
+    );
+    // An item the entity doesn't have all the stats for isn't equipped at all.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                item("Amulet of Wisdom", "neck", &[("str", 1), ("int", 5)]),
+                equip("hero", "Amulet of Wisdom")
+            ]
+        ),
+        Err(RunErr::TriedToGetNonexistentVariable)
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                write(variable("hero.str")),
+                command(Command::Let("hero.int".to_string(), value(3))),
+                equip("hero", "Amulet of Wisdom"),
+                space(),
+                write(variable("hero.str")),
+            ]
+        ),
+        Ok("28 | 29".to_string())
+    );
+
+    assert_eq!(
+        super::run(&mut storage, vec![unequip("hero", "weapon")]),
+        Err(RunErr::EmptySlot("hero.weapon".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![equip("hero", "Excalibur")]),
+        Err(RunErr::NonexistentItem("Excalibur".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![equip("villain", "Iron Sword")]),
+        Err(RunErr::NonexistentEntity("villain".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![item("Iron Sword", "weapon", &[])]),
+        Err(RunErr::TriedToRedefineItem("Iron Sword".to_string()))
+    );
+
+    // An effect or a modifier which happens to be called like an item doesn't replace what the item gives, and
+    // running out doesn't take it away either.
+    let effect = |name: &str, amount: i64| {
+        command(Command::Effect(
+            name.to_string(),
+            "hero.str".to_string(),
+            value(amount),
+            value(1),
+            TimeUnit::Ticks,
+        ))
+    };
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Modifier(
+                    "hero.str".to_string(),
+                    "class".to_string(),
+                    value(6),
+                    false
+                )),
+                effect("Ring of Might", 2),
+                effect("class", 3),
+                command(Command::Explain("hero.str".to_string())),
+                space(),
+                command(Command::Tick(value(1))),
+                command(Command::Explain("hero.str".to_string())),
+            ]
+        ),
+        Ok("hero.str 40 = 20 base + 8 [Ring of Might] + 1 [Amulet of Wisdom] + 6 class + 2 [Ring of Might] + 3 class | hero.str 35 = 20 base + 8 [Ring of Might] + 1 [Amulet of Wisdom] + 6 class".to_string())
+    );
+
+    // The ring stays in its slot, so its bonuses can only go away by unequipping it.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Revoke("Ring of Might".to_string()))]
+        ),
+        Err(RunErr::ModifierNotRevocable(
+            "Ring of Might".to_string(),
+            "an item".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                write(variable("hero.str")),
+                command(Command::Revoke("class".to_string())),
+                unequip("hero", "ring"),
+                space(),
+                write(variable("hero.str")),
+            ]
+        ),
+        Ok("35 | 21".to_string())
+    );
+}
+
+#[test]
+fn allocation() {
+    let space = || Content::Text(" ".to_string());
+    let allocate = |stat: &str, pool: &str, amount: i64| {
+        command(Command::Allocate(
+            stat.to_string(),
+            pool.to_string(),
+            value(amount),
+        ))
+    };
+    let points =
+        |pool: &str, amount: i64| command(Command::Points(pool.to_string(), value(amount)));
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.str".to_string(), value(10))),
+                command(Command::Let("hero.vit".to_string(), value(8))),
+                points("hero.points", 5),
+                allocate("hero.str", "hero.points", 3),
+                write(variable("hero.str")),
+                space(),
+                write(variable("hero.points")),
+                space(),
+                allocate("hero.vit", "hero.points", 2),
+                write(variable("hero.vit")),
+                space(),
+                write(variable("hero.points")),
+                space(),
+                // The points are a modifier on top of the stat, which adds up over several allocations.
+                command(Command::Add("hero.points".to_string(), value(4))),
+                allocate("hero.str", "hero.points", 1),
+                allocate("hero.str", "hero.points", 2),
+                command(Command::Explain("hero.str".to_string())),
+            ]
+        ),
+        Ok("13 2 10 0 hero.str 16 = 10 base + 6 allocate".to_string())
+    );
+
+    assert_eq!(
+        super::run(&mut storage, vec![allocate("hero.str", "hero.points", 2)]),
+        Err(RunErr::NotEnoughPoints(
+            "hero.points".to_string(),
+            "2".to_string(),
+            "1".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![allocate("hero.str", "hero.points", 0)]),
+        Err(RunErr::InvalidQuantity("0".to_string()))
+    );
+    // The points can't go below zero in any other way either.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Subtract(
+                "hero.points".to_string(),
+                value(10)
+            ))]
+        ),
+        Err(RunErr::NotEnoughPoints(
+            "hero.points".to_string(),
+            "10".to_string(),
+            "1".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Set("hero.points".to_string(), value(-1)))]
+        ),
+        Err(RunErr::NotEnoughPoints(
+            "hero.points".to_string(),
+            "2".to_string(),
+            "1".to_string()
+        ))
+    );
+
+    // A derived stat, like the ones of a class, can be raised too.
+    let mut classy = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut classy,
+            vec![
+                command(Command::Class(
+                    "Warrior".to_string(),
+                    vec![
+                        ("level".to_string(), value(1)),
+                        (
+                            "str".to_string(),
+                            binary(
+                                BiOperation::Add,
+                                value(10),
+                                binary(BiOperation::Multiply, variable("level"), value(2))
+                            )
+                        ),
+                    ]
+                )),
+                command(Command::New(
+                    "hero".to_string(),
+                    "Warrior".to_string(),
+                    vec![]
+                )),
+                points("hero.points", 5),
+                allocate("hero.str", "hero.points", 3),
+                write(variable("hero.str")),
+                space(),
+                command(Command::Add("hero.level".to_string(), value(1))),
+                write(variable("hero.str")),
+                space(),
+                write(variable("hero.points")),
+            ]
+        ),
+        Ok("15 17 2".to_string())
+    );
+
+    // Only the points themselves can be spent, not a bonus on top of them.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Modifier(
+                    "hero.points".to_string(),
+                    "bonus".to_string(),
+                    value(5),
+                    false
+                )),
+                allocate("hero.str", "hero.points", 4),
+            ]
+        ),
+        Err(RunErr::NotEnoughPoints(
+            "hero.points".to_string(),
+            "4".to_string(),
+            "1".to_string()
+        ))
+    );
+
+    // The stat has to be a number, and the points don't change if it isn't.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let(
+                    "hero.name".to_string(),
+                    Expression::Str("Bob".to_string())
+                )),
+                allocate("hero.name", "hero.points", 1),
+            ]
+        ),
+        Err(RunErr::TypeMismatch(
+            "hero.name".to_string(),
+            "a string".to_string(),
+            "a number".to_string()
+        ))
+    );
+    // The points have to be created as points, rather than as any other variable.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Let("hero.spare".to_string(), value(3))),
+                allocate("hero.str", "hero.spare", 3),
+            ]
+        ),
+        Err(RunErr::NotPoints("hero.spare".to_string()))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![command(Command::Revoke("allocate".to_string()))]
+        ),
+        Err(RunErr::ModifierNotRevocable(
+            "allocate".to_string(),
+            "the allocated points".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                write(variable("hero.str")),
+                space(),
+                write(variable("hero.points")),
+            ]
+        ),
+        Ok("16 6".to_string())
+    );
+}
+
+#[test]
+fn skills() {
+    let skill = |skill: &str, max: i64, prerequisites: Vec<Expression>| {
+        command(Command::Skill(skill.to_string(), value(max), prerequisites))
+    };
+    let learn = |skill: &str| command(Command::Learn("hero".to_string(), skill.to_string()));
+    let rankup = |skill: &str| command(Command::Rankup("hero".to_string(), skill.to_string()));
+
+    let mut storage = Storage::new();
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Entity("hero".to_string())),
+                command(Command::Let("hero.int".to_string(), value(25))),
+                skill("Spark", 1, vec![]),
+                skill(
+                    "Fire Ball",
+                    3,
+                    vec![
+                        variable("Spark"),
+                        // The fields of the entity can be written without its name.
+                        binary(BiOperation::GreaterOrEqual, variable("int"), value(20)),
+                    ]
+                ),
+                learn("Spark"),
+                learn("Fire Ball"),
+                rankup("Fire Ball"),
+                command(Command::Skills("hero".to_string())),
+            ]
+        ),
+        Ok("Spark 1/1, Fire Ball 2/3".to_string())
+    );
+
+    // The prerequisites are checked for every rank.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                command(Command::Set("hero.int".to_string(), value(10))),
+                rankup("Fire Ball"),
+            ]
+        ),
+        Err(RunErr::PrerequisiteNotMet(
+            "hero".to_string(),
+            "Fire Ball".to_string(),
+            "(int >= 20)".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                skill("Inferno", 1, vec![variable("Meteor")]),
+                skill("Meteor", 1, vec![]),
+                learn("Inferno"),
+            ]
+        ),
+        Err(RunErr::PrerequisiteNotMet(
+            "hero".to_string(),
+            "Inferno".to_string(),
+            "Meteor".to_string()
+        ))
+    );
+    // A prerequisite which was never defined is named in the error, along with the skill needing it.
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                skill("Firestorm", 1, vec![variable("Fire Bal")]),
+                learn("Firestorm"),
+            ]
+        ),
+        Err(RunErr::UnknownPrerequisite(
+            "Firestorm".to_string(),
+            "Fire Bal".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(
+            &mut storage,
+            vec![
+                skill("Ice Lance", 1, vec![Expression::Str("Frost".to_string())]),
+                learn("Ice Lance"),
+            ]
+        ),
+        Err(RunErr::UnknownPrerequisite(
+            "Ice Lance".to_string(),
+            "Frost".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![rankup("Spark")]),
+        Err(RunErr::MaxRankReached("Spark".to_string(), 1))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![learn("Spark")]),
+        Err(RunErr::SkillAlreadyLearned(
+            "hero".to_string(),
+            "Spark".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![rankup("Meteor")]),
+        Err(RunErr::SkillNotLearned(
+            "hero".to_string(),
+            "Meteor".to_string()
+        ))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![learn("Blizzard")]),
+        Err(RunErr::NonexistentSkill("Blizzard".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![skill("Spark", 2, vec![])]),
+        Err(RunErr::TriedToRedefineSkill("Spark".to_string()))
+    );
+    assert_eq!(
+        super::run(&mut storage, vec![skill("Blizzard", 0, vec![])]),
+        Err(RunErr::InvalidQuantity("0".to_string()))
+    );
 }
@@ -17,31 +17,68 @@ mod util;
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 {
+    // Get the locations where we should read the files from and where to save the output.
+    // Every file is a chapter, and they're all run in a row with the same storage. The rolls can be seeded with
+    // `--seed 1234` anywhere among them, so that they can be changed without touching the text.
+    let mut input_filenames: Vec<String> = Vec::new();
+    let mut seed: Option<String> = None;
+    let mut arguments = args[1..].iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "--seed" {
+            seed = Some(arguments.next().cloned().unwrap_or_default());
+        } else {
+            input_filenames.push(argument.to_owned());
+        }
+    }
+
+    if input_filenames.is_empty() {
         // This is a hack but it looks nice (better than using the return keyword)
         Err(RunErr::MissingInput)?;
     }
 
-    // Get the locations where we should read the file from and where to save it.
-    let input_filename: String = args[1].to_owned();
     let output_filename: &str = "output.txt";
+    let rolls_filename: &str = "rolls.txt";
 
-    // Try to load the file and parse it into `Content`
-    let loaded_file = fs::read_to_string(input_filename)?;
+    // The rolls of an earlier run are emptied out before anything else, so that they can't be mistaken for the
+    // rolls of this one if it fails halfway.
+    fs::write(rolls_filename, "")?;
 
-    // Try and parse the file
-    let parsed_file = parser::parseFile(loaded_file)?;
+    // Try to load the files and parse them into `Content` before running any of them
+    let mut parsed_files = Vec::new();
+    for input_filename in input_filenames {
+        let loaded_file = fs::read_to_string(input_filename)?;
+        parsed_files.push(parser::parseFile(loaded_file)?);
+    }
 
     // Initialize the storage
     let mut storage: Storage = Storage::new();
 
-    // Run the interpreter on the parsed file and pass in the mutable reference to the storage.
-    // This is going to be useful later, when we're going to be parsing and interpreting multiple files in the row
-    // while needing to retain the memory of variables initialized and modified in previous files.
-    let output = interpreter::run(&mut storage, parsed_file)?;
+    if let Some(seed) = seed {
+        storage
+            .rng()
+            .reseed(seed.parse().map_err(|_| RunErr::InvalidSeed(seed))?);
+    }
+
+    // Run the interpreter on the parsed files and pass in the mutable reference to the storage, so that the
+    // variables initialized and modified in previous files are still there. The end of every file is the end
+    // of a chapter, which the effects lasting for chapters are counted in.
+    let mut output = String::new();
+    for parsed_file in parsed_files {
+        output += &interpreter::run(&mut storage, parsed_file)?;
+        interpreter::endChapter(&mut storage)?;
+    }
 
     // Save the output to file
     fs::write(output_filename, output)?;
 
+    // Every roll goes into a file of its own, so that the author can check what was rolled.
+    let log: String = storage
+        .rng()
+        .rolls()
+        .iter()
+        .map(|roll| roll.to_string() + "\n")
+        .collect();
+    fs::write(rolls_filename, log)?;
+
     Ok(())
 }
@@ -1,8 +1,13 @@
-use std::cmp::Ordering;
-
+use crate::types::curve::{Curve, Thresholds};
+use crate::types::decimal::MAX_PRECISION;
+use crate::types::effect::TimeUnit;
+use crate::types::engine::{NumberMode, Refill, Rounding, MAX_DEPTH};
 use crate::types::error::ParsingErr;
-use crate::types::{BiOperation, Command, Content, Expression, UnOperation};
+use crate::types::number::Number;
+use crate::types::random::MAX_DICE;
+use crate::types::{BiOperation, Command, Content, Expression, Format, Setting, UnOperation};
 
+#[cfg(test)]
 mod tests;
 
 /// At the moment, this function takes in a String (which is allocated on the heap)
@@ -34,29 +39,26 @@ pub fn parseFile(inputString: String) -> Result<Vec<Content>, ParsingErr> {
     for char in inputString.clone().chars() {
         current += 1;
 
-        match char {
-            '$' => {
-                // If we encounter a $, it means that we're either at the beginning of the command, or at the end
-                // If `reading_command` flag is true, it means we were at the end of one, so try and parse it and set the flag to false.
-                // If it's false, then we're at the beginning of one, so set the flag to true.
-                let t = getter(chunk_start, current - 1)?;
-
-                let mut chunk = if reading_command {
-                    parseCommand(t).map(|commands| {
-                        commands
-                            .iter()
-                            .map(|o| Content::Command(o.clone()))
-                            .collect()
-                    })
-                } else {
-                    Ok(vec![Content::Text(t)])
-                }?;
-
-                reading_command = !reading_command;
-                result.append(&mut chunk);
-                chunk_start = current;
-            }
-            _ => {}
+        if char == '$' {
+            // If we encounter a $, it means that we're either at the beginning of the command, or at the end
+            // If `reading_command` flag is true, it means we were at the end of one, so try and parse it and set the flag to false.
+            // If it's false, then we're at the beginning of one, so set the flag to true.
+            let t = getter(chunk_start, current - 1)?;
+
+            let mut chunk = if reading_command {
+                parseCommand(t).map(|commands| {
+                    commands
+                        .iter()
+                        .map(|o| Content::Command(o.clone()))
+                        .collect()
+                })
+            } else {
+                Ok(vec![Content::Text(t)])
+            }?;
+
+            reading_command = !reading_command;
+            result.append(&mut chunk);
+            chunk_start = current;
         }
     }
 
@@ -68,53 +70,304 @@ pub fn parseFile(inputString: String) -> Result<Vec<Content>, ParsingErr> {
         if chunk_start < current {
             result.push(Content::Text(getter(chunk_start, current)?));
         }
-        Ok(result)
+        nestBlocks(result)
     }
 }
 
-fn matchWhitespace(character: char) -> bool {
-    match character {
-        ' ' => true,
-        '\n' => true,
-        _ => false,
+/// A block which hasn't been closed yet.
+struct OpenBlock {
+    /// Everything that came before the block, which the finished block gets appended to.
+    outer: Vec<Content>,
+    kind: BlockKind,
+}
+
+impl OpenBlock {
+    /// Opens the block, taking everything read so far with it.
+    fn new(current: &mut Vec<Content>, kind: BlockKind) -> OpenBlock {
+        OpenBlock {
+            outer: std::mem::take(current),
+            kind,
+        }
     }
 }
 
-fn matchCommandEnd(character: char) -> bool {
-    match character {
-        '\n' => true,
-        ';' => true,
-        _ => false,
+enum BlockKind {
+    Conditional {
+        /// The branches which already ended.
+        branches: Vec<(Expression, Vec<Content>)>,
+        /// The condition of the branch being read right now, or `None` if it's the `else`.
+        condition: Option<Expression>,
+    },
+    Repeat(Expression, Option<String>),
+    While(Expression, Option<String>),
+    Macro(String, Vec<String>),
+}
+
+/// Blocks nested deeper than this are rejected, since running them (and expanding the macros in them) goes
+/// through every level of the nesting on the stack.
+const MAX_BLOCK_DEPTH: usize = 64;
+
+/// The blocks can span several `$...$` sections, with text in between, so they're only marked by commands at first.
+/// This folds everything between the markers into a `Content::Conditional` (or a loop), checking that the blocks
+/// are balanced.
+fn nestBlocks(flat: Vec<Content>) -> Result<Vec<Content>, ParsingErr> {
+    let mut open: Vec<OpenBlock> = Vec::new();
+    let mut current: Vec<Content> = Vec::new();
+
+    for content in flat {
+        match content {
+            Content::Command(Command::If(condition)) => open.push(OpenBlock::new(
+                &mut current,
+                BlockKind::Conditional {
+                    branches: Vec::new(),
+                    condition: Some(condition),
+                },
+            )),
+            Content::Command(Command::Repeat(times, index)) => open.push(OpenBlock::new(
+                &mut current,
+                BlockKind::Repeat(times, index),
+            )),
+            Content::Command(Command::While(condition, index)) => open.push(OpenBlock::new(
+                &mut current,
+                BlockKind::While(condition, index),
+            )),
+            Content::Command(Command::Def(name, parameters)) => open.push(OpenBlock::new(
+                &mut current,
+                BlockKind::Macro(name, parameters),
+            )),
+            Content::Command(marker @ (Command::ElseIf(_) | Command::Else)) => {
+                // Both of them end the branch before them, which mustn't be the `else`.
+                let Some(OpenBlock {
+                    kind:
+                        BlockKind::Conditional {
+                            branches,
+                            condition: condition @ Some(_),
+                        },
+                    ..
+                }) = open.last_mut()
+                else {
+                    return Err(ParsingErr::UnexpectedBlockMarker(
+                        marker.keyword().to_string(),
+                    ));
+                };
+
+                let next = match marker {
+                    Command::ElseIf(condition) => Some(condition),
+                    _ => None,
+                };
+                if let Some(condition) = std::mem::replace(condition, next) {
+                    branches.push((condition, std::mem::take(&mut current)));
+                }
+            }
+            Content::Command(Command::End) => {
+                let block = open
+                    .pop()
+                    .ok_or(ParsingErr::UnexpectedBlockMarker("end".to_string()))?;
+
+                let last = std::mem::replace(&mut current, block.outer);
+                current.push(match block.kind {
+                    BlockKind::Conditional {
+                        mut branches,
+                        condition: Some(condition),
+                    } => {
+                        branches.push((condition, last));
+                        Content::Conditional(branches, Vec::new())
+                    }
+                    BlockKind::Conditional {
+                        branches,
+                        condition: None,
+                    } => Content::Conditional(branches, last),
+                    BlockKind::Repeat(times, index) => Content::Repeat(times, index, last),
+                    BlockKind::While(condition, index) => Content::While(condition, index, last),
+                    BlockKind::Macro(name, parameters) => Content::Macro(name, parameters, last),
+                });
+            }
+            other => current.push(other),
+        }
+
+        if open.len() > MAX_BLOCK_DEPTH {
+            return Err(ParsingErr::BlockTooDeep);
+        }
+    }
+
+    if open.is_empty() {
+        Ok(current)
+    } else {
+        Err(ParsingErr::BlockLeftOpen)
+    }
+}
+
+/// Splits the input at every character matching the separator, except for the ones inside of strings, so that
+/// ```let title "The Unkillable; Slayer of Gods"``` stays in one piece. Empty parts are skipped.
+///
+/// The same goes for anything inside of brackets, so that a class can be written over several lines, and the
+/// commas between its fields aren't confused with the ones between the arguments of a function.
+fn splitUnquoted(input: &str, separator: fn(char) -> bool) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut start = 0;
+
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut depth: usize = 0;
+
+    for (index, character) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && character == '\\' {
+            escaped = true;
+        } else if character == '"' {
+            quoted = !quoted;
+        } else if quoted {
+            continue;
+        } else if matches!(character, '(' | '{') {
+            depth += 1;
+        } else if matches!(character, ')' | '}') {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && separator(character) {
+            parts.push(&input[start..index]);
+            start = index + character.len_utf8();
+        }
     }
+    parts.push(&input[start..]);
+
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+/// Parses the fields of a class, written like ```{ str: 10 + level * 2, vit: 8 + level * 3 }```.
+/// A comma after the last field is allowed.
+fn parseFields(input: &str) -> Result<Vec<(String, Expression)>, ParsingErr> {
+    let inside = input
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or_else(|| ParsingErr::UnrecognizedExpression(input.to_string()))?;
+
+    let mut fields: Vec<(String, Expression)> = Vec::new();
+    for field in splitUnquoted(inside, |character| character == ',') {
+        if field.trim().is_empty() {
+            continue;
+        }
+
+        let (name, formula) = field
+            .split_once(':')
+            .ok_or_else(|| ParsingErr::UnrecognizedExpression(field.trim().to_string()))?;
+        let name = name.trim();
+
+        // The fields are the names right after the dot, so they can't have dots of their own.
+        if name.contains('.') || !isIdentifier(name) {
+            return Err(ParsingErr::UnrecognizedExpression(name.to_string()));
+        }
+        if fields.iter().any(|(existing, _)| existing == name) {
+            return Err(ParsingErr::DuplicateField(name.to_string()));
+        }
+
+        fields.push((name.to_string(), parseExpression(formula.to_string())?));
+    }
+
+    Ok(fields)
+}
+
+fn matchWhitespace(character: char) -> bool {
+    matches!(character, ' ' | '\n')
+}
+
+fn matchCommandEnd(character: char) -> bool {
+    matches!(character, '\n' | ';')
 }
 
-/// The program is meant to work on numbers, which are all stored as integers. No floating point numbers.
+/// The program is meant to work on numbers, which are stored as integers, or fixed-point decimals like `1.25` or `15%`.
+/// No floating point numbers.
+///
+/// Every value given to a command is an expression, which is evaluated when the command is run.
+/// Expressions can call built-in functions, like ```clamp(hp + 20, 0, max_hp)```.
+///
+/// Variables can hold strings as well, which are written in quotes: ```let class "Spellblade"```. A quote or a
+/// backslash inside of a string has to be escaped with a backslash, and a `$` would end the command section
+/// instead. Strings can be joined with `+` and compared with `==` and `!=`.
 ///
 /// List of commands:
 /// - let : creates a variable and initializes it with the given value. Example: ```let variable 0```
-/// - add : adds a value to a variable. Example: ```add variable 10```
-/// - subtract : subtracts a value from a variable. Example: ```sub variable 10```
-/// - set : sets a variable to a new value. Example: ```set variable -10```
+/// - add : adds a value to a variable. Example: ```add variable level * 2```
+/// - subtract : subtracts a value from a variable. Example: ```subtract variable 10```
+/// - set : sets a variable to a new value, which has to be of the same type as the old one. Example: ```set variable max_variable```
+/// - config : changes a setting of the engine. Example: ```config rounding truncate```
+/// - derive : creates a variable whose value is always computed from an expression. Since it's computed on every
+///   read, the expression can't roll dice or call `random`. Example: ```derive strength = base_strength + level * 2```
+/// - write : writes the value of an expression into the output. Example: ```write variable * 2 + 5```
+///   It can be given a format, like ```write crit_chance as decimals 2```
+/// - entity : creates an entity, whose fields are written with a dot. Example: ```entity hero; let hero.str 10```
+/// - fields : writes the fields of an entity and their values. Example: ```fields hero```
+/// - delete : deletes an entity together with all of its fields. Example: ```delete goblin_king```
+/// - class : defines a template for entities, with a formula for every field. Inside of the formulas, the names of
+///   the fields refer to the fields of the entity. Example: ```class Warrior { level: 1, str: 10 + level * 2 }```
+/// - new : creates an entity from a class, optionally overriding some of the fields. Example: ```new hero Warrior { level: 5 }```
+/// - list : creates an empty list, to hold items like an inventory. Example: ```list inventory```
+/// - push, remove : add items to a list or take them out of it, optionally with how many of them there are.
+///   Example: ```push inventory "Health Potion" x 3```
+///   The items can be counted with ```count(inventory, "Health Potion")``` and checked with ```contains(inventory, "Iron Sword")```.
+/// - def, call : `def` starts a macro, which holds everything until its `end`, and `call` runs it. The parameters
+///   stand in for whatever the macro is called with. An argument which isn't a name is evaluated once, when the
+///   macro is called, so dice in it are only rolled once. A parameter can stand for the name of an item, a skill,
+///   a slot, an effect or a modifier source too, which can be given as a string. Example: ```$def levelup(who)$$add who.level 1$Level up!$end$```
+///   and then ```call levelup(hero)```
+/// - pool : creates a full pool, like health or mana, with the given maximum. Adding and subtracting keeps it
+///   between zero and the maximum, which can be changed through `hp.max`. It can be read as `hp`, `hp.max` and
+///   `hp.percent`, and is written like ```73/120```. Example: ```pool hp vit * 10```
+/// - effect : changes a number for a while, like a buff or a debuff, as a modifier named after the effect, which is
+///   removed once it runs out. On a pool, the change it actually made is taken back instead. Starting an effect again
+///   renews it. Its name never clashes with an item or a `modifier` source of the same name. The duration is counted in ticks, unless it's followed by `chapters`.
+///   Example: ```effect "Blessing of Dawn" hero.str 5 for 3 ticks```
+/// - tick, advance : counts the effects down by this many ticks, or by one. Example: ```tick 2```
+/// - chapter : counts down the effects lasting for chapters. It happens on its own between the input files.
+/// - effects : writes the active effects, either all of them or the ones on a variable or entity. Example: ```effects hero```
+/// - curve : gives the experience a curve, so that gaining it raises the level as many times as the thresholds
+///   allow. The formula gives the total experience needed for a level, with the name of the level standing for it,
+///   or a table lists them, starting with the first level. A macro given after `then` is called after every level
+///   gained. Example: ```curve xp level = 100 * (level - 1) ^ 2 then levelup(hero)``` or ```curve xp level = table 0, 100, 300```
+/// - modifier : adds a modifier to a stat, from a source like the class or a piece of equipment. The flat modifiers
+///   are added to the base value of the stat, and the percentages are summed up and applied after them. A modifier
+///   from the same source replaces the old one. Example: ```modifier str "Ring of Might" 8``` or ```modifier str haste 10 percent```, which can also be written as ```modifier str haste 10%```
+/// - revoke : removes the modifiers from a source, from every stat. Items and effects take theirs away on their own,
+///   when they're unequipped or run out, so their modifiers can't be revoked. Example: ```revoke "Ring of Might"```
+/// - explain : writes how a stat is made up. Example: ```explain str``` gives ```str 34 = 20 base + 6 class + 8 [Ring of Might]```
+/// - item : defines an item, with the slot it goes into and the bonuses it gives to the stats of whoever wears it.
+///   Example: ```item "Iron Sword" weapon { str: 5, agi: 2 }```
+/// - equip, unequip : put an item on an entity, or take it out of a slot, adding or removing all of its bonuses
+///   as modifiers. A slot holds one item at a time. Example: ```equip hero "Iron Sword"``` and ```unequip hero weapon```
+/// - points : creates unspent points, which can be gained with `add` like any number, but never go below zero.
+///   Example: ```points hero.points 5```
+/// - allocate : moves unspent points into a stat, failing if there aren't enough of them. The points are taken from
+///   `points`, or the `points` field of the entity, like `hero.points` for `hero.str`, unless others are given after
+///   `from`, and they have to be created with `points`. The stat gets them as a modifier from `allocate`, so even a
+///   derived stat, like one of a class, can be raised. Modifiers on the points can't be spent.
+///   Example: ```points hero.points 5; allocate 3 hero.str; write hero.points``` or ```allocate 2 vit from bonus_points```
+/// - skill : defines a skill, with its maximum rank and optionally its prerequisites after `requires`. A prerequisite
+///   can be another skill, which has to be learned first, or a condition, in which the fields of the entity can be
+///   written without its name. Example: ```skill Fireball 5 requires Spark, int >= 20```
+/// - learn, rankup : give an entity the first rank of a skill, or raise the rank it has, as long as it meets the
+///   prerequisites. Example: ```learn hero Fireball```
+/// - skills : writes the skills an entity has learned, with their ranks. Example: ```skills hero``` gives ```Spark 1/1, Fireball 2/5```
+/// - roll : writes the value of an expression like `write`, and records it among the rolls, as a single entry with
+///   every die rolled for it and the total. Example: ```roll 2d6 + 3```
+/// - seed : starts the rolls over from the given seed, so that they come out the same every time. Example: ```seed 1234```
+/// - if, elseif, else, end : only the text and commands of the first branch whose condition holds are used.
+///   The blocks can span several command sections, like ```$if int >= 20$[Skill Unlocked: Fireball]$end$```
+/// - repeat, while : run everything until the `end` a given number of times, or for as long as a condition holds.
+///   The index of the loop (counting from 1) can be given a name, which can't be taken by another variable. Example: ```$repeat 12 as day$Day $write day$$end$```
 fn parseCommand(input: String) -> Result<Vec<Command>, ParsingErr> {
-    let statements: Vec<&str> = input
-        .split(matchCommandEnd)
-        .filter(|c| !c.is_empty())
-        .collect();
+    let statements: Vec<&str> = splitUnquoted(&input, matchCommandEnd);
 
     let mut result_commands: Vec<Command> = Vec::new();
 
     for com in statements {
-        let words: Vec<&str> = com
-            .split(matchWhitespace)
-            .filter(|c| !c.is_empty())
-            .collect();
+        let words: Vec<&str> = splitUnquoted(com, matchWhitespace);
 
         let amount_of_words = words.len();
 
-        // This is a tiny function I made to check if the number of words is equal to the expected.
-        // This could of course be done in each match case, but this way it's much less code repetition.
+        // This is a tiny function I made to check if the number of words is at least the expected.
+        // Since the last argument of most commands is an expression, which can span any amount of words,
+        // the exact number of words can't be checked.
         let checkNumOfArguments = |expectedNumOfWords: usize| {
-            if expectedNumOfWords == amount_of_words - 1 {
+            if expectedNumOfWords < amount_of_words {
                 Ok(())
             } else {
                 Err(ParsingErr::InvalidNumberOfArguments)
@@ -127,195 +380,683 @@ fn parseCommand(input: String) -> Result<Vec<Command>, ParsingErr> {
             return Err(ParsingErr::InvalidNumberOfArguments);
         }
 
+        // Everything after the variable name is the expression giving the value.
+        let valueExpression = || parseExpression(words[2..].join(" "));
+
         let command = match words[0] {
             "let" => {
                 checkNumOfArguments(2)?;
-                Ok(Command::Let(words[1].to_string(), words[2].parse::<i32>()?))
+                Ok(Command::Let(words[1].to_string(), valueExpression()?))
             }
 
             "add" => {
                 checkNumOfArguments(2)?;
-                Ok(Command::Add(words[1].to_string(), words[2].parse::<i32>()?))
+                Ok(Command::Add(words[1].to_string(), valueExpression()?))
             }
             "subtract" => {
                 checkNumOfArguments(2)?;
-                Ok(Command::Subtract(
+                Ok(Command::Subtract(words[1].to_string(), valueExpression()?))
+            }
+            "set" => {
+                checkNumOfArguments(2)?;
+                Ok(Command::Set(words[1].to_string(), valueExpression()?))
+            }
+            "derive" => {
+                // The `=` is only there to make the command easier to read, so it's simply checked and skipped.
+                checkNumOfArguments(3)?;
+                if words[2] != "=" {
+                    return Err(ParsingErr::UnrecognizedExpression(words[2].to_string()));
+                }
+                Ok(Command::Derive(
                     words[1].to_string(),
-                    words[2].parse::<i32>()?,
+                    parseExpression(words[3..].join(" "))?,
                 ))
             }
-            "set" => {
+            "config" => {
                 checkNumOfArguments(2)?;
-                Ok(Command::Set(words[1].to_string(), words[2].parse::<i32>()?))
+                Ok(Command::Config(parseSetting(words[1], words[2])?))
             }
-            "write" => {
-                // As the write command can take in expressions now, there is no check for the
-                // number of arguments.
-                let _a = parseExpression(words[1..].concat())?;
-                Ok(Command::Write(words[1].to_string()))
+            "entity" | "fields" | "delete" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+
+                let entity = words[1].to_string();
+                Ok(match words[0] {
+                    "entity" => Command::Entity(entity),
+                    "fields" => Command::Fields(entity),
+                    _ => Command::Delete(entity),
+                })
             }
-            other_command => Err(ParsingErr::UnrecognizedCommand(other_command.to_string())),
-        }?;
+            "class" => {
+                if amount_of_words != 3 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                Ok(Command::Class(words[1].to_string(), parseFields(words[2])?))
+            }
+            "new" => {
+                // The overrides can be left out, in which case the entity is exactly what the class says.
+                let overrides = match amount_of_words {
+                    3 => Vec::new(),
+                    4 => parseFields(words[3])?,
+                    _ => return Err(ParsingErr::InvalidNumberOfArguments),
+                };
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+                Ok(Command::New(
+                    words[1].to_string(),
+                    words[2].to_string(),
+                    overrides,
+                ))
+            }
+            "list" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+                Ok(Command::List(words[1].to_string()))
+            }
+            "push" | "remove" => {
+                checkNumOfArguments(2)?;
 
-        result_commands.push(command);
-    }
+                // The item can be followed by `x` and how many of it there are, which is one if it isn't given.
+                let (item, quantity) = match words[3..].iter().rposition(|word| *word == "x") {
+                    Some(split) => (
+                        parseExpression(words[2..split + 3].join(" "))?,
+                        parseExpression(words[split + 4..].join(" "))?,
+                    ),
+                    None => (valueExpression()?, Expression::Value(Number::from(1))),
+                };
 
-    Ok(result_commands)
-}
+                let list = words[1].to_string();
+                Ok(match words[0] {
+                    "push" => Command::Push(list, item, quantity),
+                    _ => Command::Remove(list, item, quantity),
+                })
+            }
+            "def" => {
+                checkNumOfArguments(1)?;
+                let (name, parameters) = parseSignature(words[1..].join(" "))?;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Op {
-    Binary(BiOperation),
-    Unary(UnOperation),
-    LeftBracket,
-    RightBracket,
-}
+                let mut names: Vec<String> = Vec::new();
+                for parameter in parameters {
+                    match parameter {
+                        // The parameters stand in for whole names, so they can't have dots of their own.
+                        Expression::Variable(parameter) if !parameter.contains('.') => {
+                            if names.contains(&parameter) {
+                                return Err(ParsingErr::DuplicateParameter(parameter));
+                            }
+                            names.push(parameter);
+                        }
+                        other => {
+                            return Err(ParsingErr::UnrecognizedExpression(other.to_string()));
+                        }
+                    }
+                }
+                Ok(Command::Def(name, names))
+            }
+            "call" => {
+                checkNumOfArguments(1)?;
+                let (name, arguments) = parseSignature(words[1..].join(" "))?;
+                Ok(Command::Call(name, arguments))
+            }
+            "pool" => {
+                checkNumOfArguments(2)?;
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+                Ok(Command::Pool(words[1].to_string(), valueExpression()?))
+            }
+            "effect" => {
+                // The name can be a string, for names with spaces like "Blessing of Dawn", and the duration
+                // comes after the last `for`, so that the amount can't be confused with it.
+                checkNumOfArguments(5)?;
+                let name = parseName(words[1])?;
+                if !isIdentifier(words[2]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[2].to_string()));
+                }
 
-impl PartialOrd for Op {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self == other {
-            Some(Ordering::Equal)
-        } else {
-            match self {
-                Op::Binary(BiOperation::Add) | Op::Binary(BiOperation::Subtract) => {
-                    if other == &Op::Binary(BiOperation::Add)
-                        || other == &Op::Binary(BiOperation::Subtract)
-                    {
-                        Some(Ordering::Equal)
-                    } else {
-                        Some(Ordering::Less)
+                let split = match words[4..].iter().rposition(|word| *word == "for") {
+                    Some(split) => split + 4,
+                    None => return Err(ParsingErr::InvalidNumberOfArguments),
+                };
+                let (duration, unit) = match words[split + 1..] {
+                    [.., "tick" | "ticks"] => (&words[split + 1..words.len() - 1], TimeUnit::Ticks),
+                    [.., "chapter" | "chapters"] => {
+                        (&words[split + 1..words.len() - 1], TimeUnit::Chapters)
                     }
+                    _ => (&words[split + 1..], TimeUnit::Ticks),
+                };
+
+                Ok(Command::Effect(
+                    name,
+                    words[2].to_string(),
+                    parseExpression(words[3..split].join(" "))?,
+                    parseExpression(duration.join(" "))?,
+                    unit,
+                ))
+            }
+            // A tick is whatever the author decides it is, so it can be called `advance` as well.
+            "tick" | "advance" => match amount_of_words {
+                1 => Ok(Command::Tick(Expression::Value(Number::from(1)))),
+                _ => Ok(Command::Tick(parseExpression(words[1..].join(" "))?)),
+            },
+            "chapter" if amount_of_words == 1 => Ok(Command::Chapter),
+            "chapter" => Err(ParsingErr::InvalidNumberOfArguments),
+            "effects" => match amount_of_words {
+                1 => Ok(Command::Effects(None)),
+                2 if isIdentifier(words[1]) => Ok(Command::Effects(Some(words[1].to_string()))),
+                2 => Err(ParsingErr::UnrecognizedExpression(words[1].to_string())),
+                _ => Err(ParsingErr::InvalidNumberOfArguments),
+            },
+            "curve" => {
+                // Written like `derive`, with the name of the level between the experience and the `=`.
+                // The macro to call after every level gained comes after the last `then`.
+                checkNumOfArguments(4)?;
+                if words[3] != "=" {
+                    return Err(ParsingErr::UnrecognizedExpression(words[3].to_string()));
                 }
-                Op::Binary(BiOperation::Multiply) => {
-                    if other == &Op::Binary(BiOperation::Add)
-                        || other == &Op::Binary(BiOperation::Subtract)
-                    {
-                        Some(Ordering::Greater)
-                    } else {
-                        Some(Ordering::Less)
+                if let Some(name) = words[1..3].iter().find(|name| !isIdentifier(name)) {
+                    return Err(ParsingErr::UnrecognizedExpression(name.to_string()));
+                }
+
+                let (thresholds, hook) = match words[4..].iter().rposition(|word| *word == "then") {
+                    Some(split) => (
+                        &words[4..split + 4],
+                        Some(parseSignature(words[split + 5..].join(" "))?),
+                    ),
+                    None => (&words[4..], None),
+                };
+                let thresholds = match thresholds {
+                    ["table", entries @ ..] if !entries.is_empty() => Thresholds::Table(
+                        splitUnquoted(&entries.join(" "), |character| character == ',')
+                            .into_iter()
+                            .map(|entry| parseExpression(entry.to_string()))
+                            .collect::<Result<Vec<Expression>, ParsingErr>>()?,
+                    ),
+                    formula => Thresholds::Formula(parseExpression(formula.join(" "))?),
+                };
+
+                Ok(Command::Curve(
+                    words[1].to_string(),
+                    Curve {
+                        level: words[2].to_string(),
+                        thresholds,
+                        hook,
+                    },
+                ))
+            }
+            "modifier" => {
+                // The amount is a percentage if it's followed by `percent`, or if it ends with a `%`. In any other
+                // expression `10%` is simply `0.1`, but here it would be a flat bonus of a tenth, which is never
+                // what's meant, so the sign is taken off and the modifier becomes a percentage of 10.
+                checkNumOfArguments(3)?;
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+
+                let (amount, percent) = match words[3..] {
+                    [ref amount @ .., "percent"] if !amount.is_empty() => (amount.join(" "), true),
+                    [ref amount @ .., last] if last.len() > 1 && last.ends_with('%') => {
+                        let mut amount = amount.to_vec();
+                        amount.push(&last[..last.len() - 1]);
+                        (amount.join(" "), true)
                     }
+                    ref amount => (amount.join(" "), false),
+                };
+                Ok(Command::Modifier(
+                    words[1].to_string(),
+                    parseName(words[2])?,
+                    parseExpression(amount)?,
+                    percent,
+                ))
+            }
+            "revoke" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
                 }
-                Op::Binary(BiOperation::Exponentiate) => {
-                    if other == &Op::LeftBracket {
-                        Some(Ordering::Less)
-                    } else {
-                        Some(Ordering::Greater)
+                Ok(Command::Revoke(parseName(words[1])?))
+            }
+            "explain" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+                Ok(Command::Explain(words[1].to_string()))
+            }
+            "item" => {
+                if amount_of_words != 4 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                // The slot is a field of the entities, like `hero.weapon`, so it's a single name without dots.
+                if words[2].contains('.') || !isIdentifier(words[2]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[2].to_string()));
+                }
+                Ok(Command::Item(
+                    parseName(words[1])?,
+                    words[2].to_string(),
+                    parseFields(words[3])?,
+                ))
+            }
+            "equip" | "unequip" => {
+                if amount_of_words != 3 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+
+                let entity = words[1].to_string();
+                match words[0] {
+                    "equip" => Ok(Command::Equip(entity, parseName(words[2])?)),
+                    _ if words[2].contains('.') || !isIdentifier(words[2]) => {
+                        Err(ParsingErr::UnrecognizedExpression(words[2].to_string()))
                     }
+                    _ => Ok(Command::Unequip(entity, words[2].to_string())),
+                }
+            }
+            "points" => {
+                checkNumOfArguments(2)?;
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
                 }
-                Op::Unary(UnOperation::Minus) => {
-                    if other == &Op::LeftBracket || other == &Op::Binary(BiOperation::Exponentiate)
-                    {
-                        Some(Ordering::Less)
-                    } else {
-                        Some(Ordering::Greater)
+                Ok(Command::Points(words[1].to_string(), valueExpression()?))
+            }
+            "allocate" => {
+                // The points come from `points`, or from the `points` of the entity if the stat is one of its fields,
+                // unless another pool is given after `from`.
+                checkNumOfArguments(2)?;
+                let (amount, stat, pool) = match words[..] {
+                    [_, ref amount @ .., stat, "from", pool] if !amount.is_empty() => {
+                        (amount, stat, pool.to_string())
                     }
+                    [_, ref amount @ .., stat] => (
+                        amount,
+                        stat,
+                        match stat.rsplit_once('.') {
+                            Some((entity, _)) => format!("{entity}.points"),
+                            None => String::from("points"),
+                        },
+                    ),
+                    _ => return Err(ParsingErr::InvalidNumberOfArguments),
+                };
+
+                if let Some(name) = [stat, pool.as_str()]
+                    .into_iter()
+                    .find(|name| !isIdentifier(name))
+                {
+                    return Err(ParsingErr::UnrecognizedExpression(name.to_string()));
                 }
-                Op::LeftBracket => Some(Ordering::Greater),
-                Op::RightBracket => Some(Ordering::Less),
+                Ok(Command::Allocate(
+                    stat.to_string(),
+                    pool,
+                    parseExpression(amount.join(" "))?,
+                ))
             }
-        }
+            "skill" => {
+                // The prerequisites come after `requires`, separated by commas.
+                checkNumOfArguments(2)?;
+                let (max, prerequisites) = match words.iter().position(|word| *word == "requires") {
+                    Some(split) => (
+                        &words[2..split],
+                        splitUnquoted(&words[split + 1..].join(" "), |character| character == ',')
+                            .into_iter()
+                            .map(|prerequisite| parseExpression(prerequisite.to_string()))
+                            .collect::<Result<Vec<Expression>, ParsingErr>>()?,
+                    ),
+                    None => (&words[2..], Vec::new()),
+                };
+
+                Ok(Command::Skill(
+                    parseName(words[1])?,
+                    parseExpression(max.join(" "))?,
+                    prerequisites,
+                ))
+            }
+            "learn" | "rankup" => {
+                if amount_of_words != 3 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+
+                let (entity, skill) = (words[1].to_string(), parseName(words[2])?);
+                Ok(match words[0] {
+                    "learn" => Command::Learn(entity, skill),
+                    _ => Command::Rankup(entity, skill),
+                })
+            }
+            "skills" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                if !isIdentifier(words[1]) {
+                    return Err(ParsingErr::UnrecognizedExpression(words[1].to_string()));
+                }
+                Ok(Command::Skills(words[1].to_string()))
+            }
+            "seed" => {
+                if amount_of_words != 2 {
+                    return Err(ParsingErr::InvalidNumberOfArguments);
+                }
+                words[1]
+                    .parse::<u64>()
+                    .map(Command::Seed)
+                    .map_err(|_| ParsingErr::UnrecognizedExpression(words[1].to_string()))
+            }
+            "roll" => {
+                checkNumOfArguments(1)?;
+                Ok(Command::Roll(parseExpression(words[1..].join(" "))?))
+            }
+            "if" => {
+                checkNumOfArguments(1)?;
+                Ok(Command::If(parseExpression(words[1..].join(" "))?))
+            }
+            // Both `elseif` and `else if` are accepted, since it's easy to write either of them.
+            "elseif" => {
+                checkNumOfArguments(1)?;
+                Ok(Command::ElseIf(parseExpression(words[1..].join(" "))?))
+            }
+            "else" if words.get(1) == Some(&"if") => {
+                checkNumOfArguments(2)?;
+                Ok(Command::ElseIf(parseExpression(words[2..].join(" "))?))
+            }
+            "else" if amount_of_words == 1 => Ok(Command::Else),
+            "end" if amount_of_words == 1 => Ok(Command::End),
+            "else" | "end" => Err(ParsingErr::InvalidNumberOfArguments),
+            "repeat" => {
+                checkNumOfArguments(1)?;
+                let (times, index) = parseLoop(&words[1..])?;
+                Ok(Command::Repeat(times, index))
+            }
+            "while" => {
+                checkNumOfArguments(1)?;
+                let (condition, index) = parseLoop(&words[1..])?;
+                Ok(Command::While(condition, index))
+            }
+            "write" => {
+                // As the write command can take in expressions now, there is no check for the
+                // number of arguments. The expression can be followed by `as` and the format to write it in.
+                let (expression, format) = match words.iter().position(|word| *word == "as") {
+                    Some(split) => (&words[1..split], parseFormat(&words[split + 1..])?),
+                    None => (&words[1..], Format::Plain),
+                };
+                Ok(Command::Write(
+                    parseExpression(expression.join(" "))?,
+                    format,
+                ))
+            }
+            other_command => Err(ParsingErr::UnrecognizedCommand(other_command.to_string())),
+        }?;
+
+        result_commands.push(command);
     }
+
+    Ok(result_commands)
 }
 
-fn toOperation(lastOperation: bool, i: char) -> Result<Op, ParsingErr> {
-    match i {
-        '(' => Ok(Op::LeftBracket),
-        ')' => Ok(Op::RightBracket),
-        '-' => Ok(if lastOperation {
-            Op::Unary(UnOperation::Minus)
-        } else {
-            Op::Binary(BiOperation::Subtract)
-        }),
-        '+' => Ok(Op::Binary(BiOperation::Add)),
-        '*' => Ok(Op::Binary(BiOperation::Multiply)),
-        '^' => Ok(Op::Binary(BiOperation::Exponentiate)),
-        _ => Err(ParsingErr::ExpressionParsing),
+/// Reads a name which is only used for display, like the name of an effect, which can be a string if it has
+/// spaces in it, like ```"Blessing of Dawn"```, or a plain word.
+fn parseName(word: &str) -> Result<String, ParsingErr> {
+    match parseExpression(word.to_string())? {
+        Expression::Str(name) => Ok(name),
+        Expression::Variable(name) if isIdentifier(&name) => Ok(name),
+        other => Err(ParsingErr::UnrecognizedExpression(other.to_string())),
     }
 }
 
-fn collapseOperation(
-    expressions_stack: &mut Vec<Expression>,
-    op_stack: &mut Vec<Op>,
-) -> Result<Expression, ParsingErr> {
-    let popped = |stack: &mut Vec<Expression>| -> Result<Box<Expression>, ParsingErr> {
-        Ok(Box::new(stack.pop().ok_or(ParsingErr::ExpressionParsing)?))
-    };
+/// Reads the name of a macro and what's in the brackets after it, which can be left out if it's empty,
+/// like ```levelup(hero, 2)``` or ```recap```.
+fn parseSignature(input: String) -> Result<(String, Vec<Expression>), ParsingErr> {
+    match parseExpression(input.clone())? {
+        Expression::Call(name, arguments) if !name.contains('.') => Ok((name, arguments)),
+        Expression::Variable(name) if !name.contains('.') => Ok((name, Vec::new())),
+        _ => Err(ParsingErr::UnrecognizedExpression(input)),
+    }
+}
 
-    let result = match op_stack.pop() {
-        Some(Op::Binary(bi_operation)) => {
-            let rhand = popped(expressions_stack)?;
-            let lhand = popped(expressions_stack)?;
-            Ok(Expression::Binary(bi_operation, lhand, rhand))
-        }
-        Some(Op::Unary(un_operation)) => {
-            Ok(Expression::Unary(un_operation, popped(expressions_stack)?))
-        }
-        Some(Op::LeftBracket) => todo!(),
-        Some(Op::RightBracket) => collapseOperation(expressions_stack, op_stack),
-        None => Err(ParsingErr::ExpressionParsing),
-    }?;
+/// The expression of a loop can be followed by `as` and the name of the variable holding the index.
+fn parseLoop(words: &[&str]) -> Result<(Expression, Option<String>), ParsingErr> {
+    match words.iter().position(|word| *word == "as") {
+        Some(split) => match &words[split + 1..] {
+            [name] if isIdentifier(name) => Ok((
+                parseExpression(words[..split].join(" "))?,
+                Some(name.to_string()),
+            )),
+            other => Err(ParsingErr::UnrecognizedExpression(other.join(" "))),
+        },
+        None => Ok((parseExpression(words.join(" "))?, None)),
+    }
+}
 
-    Ok(result)
+/// The formats a value can be written in:
+/// - decimals N : with exactly N decimal places. Example: ```write damage_multiplier as decimals 2```
+/// - bullets : a list with every item on its own line. Example: ```write inventory as bullets```
+///
+/// Without a format, a list is written with its items separated by commas.
+fn parseFormat(words: &[&str]) -> Result<Format, ParsingErr> {
+    match words {
+        ["bullets"] => Ok(Format::Bullets),
+        ["decimals", decimals] => decimals
+            .parse::<u32>()
+            .ok()
+            .filter(|decimals| *decimals <= MAX_PRECISION)
+            .map(Format::Decimals)
+            .ok_or(ParsingErr::UnrecognizedFormat(words.join(" "))),
+        _ => Err(ParsingErr::UnrecognizedFormat(words.join(" "))),
+    }
 }
 
-fn parseExpression(input: String) -> Result<Expression, ParsingErr> {
-    let tokens: Vec<Token> = tokenizeExpression(input)?;
+/// Settings are given as a name and a value, both of which are single words.
+///
+/// List of settings:
+/// - rounding : how the result of a division is rounded, one of `floor`, `truncate` and `round`.
+/// - numbers : `fixed` for 64-bit numbers, or `big` for numbers of any size.
+/// - precision : how many decimal places are kept when multiplying or dividing decimals, up to 18.
+/// - iterations : how many times a single loop can run, or how many levels a curve can give at once, before it's
+///   stopped with an error.
+/// - depth : how many macro calls can run inside of each other, up to 128.
+/// - refill : what happens to a pool when its maximum changes, `keep` to keep the current value, or `proportional`
+///   to keep it as full as it was.
+fn parseSetting(name: &str, value: &str) -> Result<Setting, ParsingErr> {
+    match (name, value) {
+        ("rounding", "floor") => Ok(Setting::Rounding(Rounding::Floor)),
+        ("rounding", "truncate") => Ok(Setting::Rounding(Rounding::Truncate)),
+        ("rounding", "round") => Ok(Setting::Rounding(Rounding::Round)),
+        ("numbers", "fixed") => Ok(Setting::Numbers(NumberMode::Fixed)),
+        ("numbers", "big") => Ok(Setting::Numbers(NumberMode::Big)),
+        ("precision", digits) => digits
+            .parse::<u32>()
+            .ok()
+            .filter(|precision| *precision <= MAX_PRECISION)
+            .map(Setting::Precision)
+            .ok_or(ParsingErr::UnrecognizedSetting(format!("{name} {value}"))),
+        ("iterations", limit) => limit
+            .parse::<u32>()
+            .map(Setting::Iterations)
+            .map_err(|_| ParsingErr::UnrecognizedSetting(format!("{name} {value}"))),
+        ("refill", "keep") => Ok(Setting::Refill(Refill::Keep)),
+        ("refill", "proportional") => Ok(Setting::Refill(Refill::Proportional)),
+        ("depth", limit) => limit
+            .parse::<u32>()
+            .ok()
+            .filter(|depth| *depth <= MAX_DEPTH)
+            .map(Setting::Depth)
+            .ok_or(ParsingErr::UnrecognizedSetting(format!("{name} {value}"))),
+        _ => Err(ParsingErr::UnrecognizedSetting(format!("{name} {value}"))),
+    }
+}
+
+/// Expressions nested deeper than this are rejected, so that a malicious (or just very unlucky) input can't
+/// overflow the stack of the recursive parser.
+const MAX_EXPRESSION_DEPTH: usize = 256;
+
+/// The binding power of the unary minus and `!`. They bind more strongly than multiplication, but less than
+/// exponentiation, so `-2 ^ 2` is `-(2 ^ 2)` like in regular math.
+const PREFIX_BINDING_POWER: u8 = 13;
+
+/// Returns the operation of a binary operator together with its left and right binding power.
+///
+/// The operator binds to the side with the higher power, which is how associativity is encoded:
+/// everything but `^` binds more to the right, so it's left-associative, while `^` is the other way around.
+///
+/// From the loosest to the tightest, it goes `||`, `&&`, equality, comparisons, and then the arithmetic,
+/// so `hp <= 0 || level >= 10 && mana > 5` reads the way it would in most programming languages.
+fn infixBindingPower(operator: &str) -> Option<(BiOperation, u8, u8)> {
+    match operator {
+        "||" => Some((BiOperation::Or, 1, 2)),
+        "&&" => Some((BiOperation::And, 3, 4)),
+        "==" => Some((BiOperation::Equal, 5, 6)),
+        "!=" => Some((BiOperation::NotEqual, 5, 6)),
+        "<" => Some((BiOperation::Less, 7, 8)),
+        "<=" => Some((BiOperation::LessOrEqual, 7, 8)),
+        ">" => Some((BiOperation::Greater, 7, 8)),
+        ">=" => Some((BiOperation::GreaterOrEqual, 7, 8)),
+        "+" => Some((BiOperation::Add, 9, 10)),
+        "-" => Some((BiOperation::Subtract, 9, 10)),
+        "*" => Some((BiOperation::Multiply, 11, 12)),
+        "/" => Some((BiOperation::Divide, 11, 12)),
+        "%" => Some((BiOperation::Modulo, 11, 12)),
+        "^" => Some((BiOperation::Exponentiate, 16, 15)),
+        _ => None,
+    }
+}
 
-    let mut operations_stack: Vec<Op> = Vec::new();
-    let mut operands_stack: Vec<Expression> = Vec::new();
+/// A Pratt (precedence climbing) parser which turns the tokens into an `Expression` tree.
+struct ExpressionParser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
 
-    let mut last_token_was_operator = false;
+impl ExpressionParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
 
-    let shouldCollapse = |stack: &Vec<Op>, op: &Op| -> bool {
-        match stack.last() {
-            Some(stack_operation) => stack_operation < op,
-            None => false,
+    /// Parses an expression in which every operator binds at least as strongly as `min_power`.
+    fn parse(&mut self, min_power: u8) -> Result<Expression, ParsingErr> {
+        self.depth += 1;
+        if self.depth > MAX_EXPRESSION_DEPTH {
+            return Err(ParsingErr::ExpressionTooDeep);
         }
-    };
 
-    for t in tokens.iter() {
-        match t {
-            Token::Number(num) => {
-                operands_stack.push(Expression::Value(*num));
-                last_token_was_operator = false;
-            }
-            Token::Variable(var) => {
-                operands_stack.push(Expression::Variable(var.to_owned()));
-                last_token_was_operator = false;
+        let mut lhand = self.parsePrefix()?;
+
+        while let Some(Token::Operator(operator)) = self.peek() {
+            let (operation, left_power, right_power) = match infixBindingPower(operator) {
+                Some(powers) => powers,
+                // Closing brackets and commas end the expression, they're handled by whoever opened the bracket.
+                None if matches!(*operator, ")" | ",") => break,
+                None => return Err(ParsingErr::ExpressionParsing),
+            };
+
+            if left_power < min_power {
+                break;
             }
-            Token::Operator(o) => {
-                let operation = toOperation(last_token_was_operator, *o)?;
 
-                if shouldCollapse(&operations_stack, &operation) {
-                    // if the operation on the stack is of higher priority, pop the operands from the stack
-                    // combine them into a new expression and put it back on the stack.
-                    let new_op = collapseOperation(&mut operands_stack, &mut operations_stack)?;
+            self.next();
+            let rhand = self.parse(right_power)?;
+            lhand = Expression::Binary(operation, Box::new(lhand), Box::new(rhand));
+        }
 
-                    operands_stack.push(new_op);
-                } else {
-                    // if the operation on the stack is of lower priority, continue onwards.
-                    operations_stack.push(operation);
-                }
+        self.depth -= 1;
+        Ok(lhand)
+    }
+
+    /// Parses the arguments of a function call, separated by commas, up to and including the closing bracket.
+    fn parseArguments(&mut self) -> Result<Vec<Expression>, ParsingErr> {
+        let mut arguments = Vec::new();
 
-                last_token_was_operator = true;
+        if self.peek() == Some(&Token::Operator(")")) {
+            self.next();
+            return Ok(arguments);
+        }
+
+        loop {
+            arguments.push(self.parse(0)?);
+
+            match self.next() {
+                Some(Token::Operator(",")) => continue,
+                Some(Token::Operator(")")) => return Ok(arguments),
+                _ => return Err(ParsingErr::ExpressionParsing),
             }
         }
     }
 
-    while operands_stack.len() > 1 {
-        let new_op = collapseOperation(&mut operands_stack, &mut operations_stack)?;
-
-        operands_stack.push(new_op);
+    /// Parses a single operand: a number, a variable, a function call, a bracketed expression or a negated operand.
+    /// Both the minus and `!` count as negation.
+    fn parsePrefix(&mut self) -> Result<Expression, ParsingErr> {
+        match self.next() {
+            Some(Token::Number(num)) => Ok(Expression::Value(num)),
+            Some(Token::Str(text)) => Ok(Expression::Str(text)),
+            Some(Token::Dice(count, sides)) => Ok(Expression::Dice(count, sides)),
+            Some(Token::Function(name)) => Ok(Expression::Call(name, self.parseArguments()?)),
+            Some(Token::Variable(var)) => Ok(Expression::Variable(var)),
+            Some(Token::Operator("(")) => {
+                let inner = self.parse(0)?;
+                match self.next() {
+                    Some(Token::Operator(")")) => Ok(inner),
+                    _ => Err(ParsingErr::ExpressionParsing),
+                }
+            }
+            Some(Token::Operator("-")) => match self.parse(PREFIX_BINDING_POWER)? {
+                // A minus right in front of a number is simply a negative number.
+                Expression::Value(num) => Ok(match num.neg() {
+                    Some(negative) => Expression::Value(negative),
+                    None => Expression::Unary(UnOperation::Minus, Box::new(Expression::Value(num))),
+                }),
+                operand => Ok(Expression::Unary(UnOperation::Minus, Box::new(operand))),
+            },
+            Some(Token::Operator("!")) => Ok(Expression::Unary(
+                UnOperation::Not,
+                Box::new(self.parse(PREFIX_BINDING_POWER)?),
+            )),
+            _ => Err(ParsingErr::ExpressionParsing),
+        }
     }
+}
+
+fn parseExpression(input: String) -> Result<Expression, ParsingErr> {
+    let mut parser = ExpressionParser {
+        tokens: tokenizeExpression(input)?,
+        position: 0,
+        depth: 0,
+    };
 
-    operands_stack.pop().ok_or(ParsingErr::ExpressionParsing)
+    let expression = parser.parse(0)?;
+
+    // If there are any tokens left, something (most likely a closing bracket) didn't belong to the expression.
+    match parser.peek() {
+        None => Ok(expression),
+        Some(_) => Err(ParsingErr::ExpressionParsing),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Token {
-    Number(i32),
-    Operator(char),
+    Number(Number),
+    Operator(&'static str),
     Variable(String),
+    /// The name of a function together with the opening bracket right after it, like `max(`.
+    Function(String),
+    Str(String),
+    Dice(u32, u32),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -333,31 +1074,89 @@ fn tokenizeExpression(input: String) -> Result<Vec<Token>, ParsingErr> {
 
     let createToken = |t: &mut Vec<Token>, buff: &mut String| -> Result<(), ParsingErr> {
         if !buff.is_empty() {
-            Ok(t.push(parseToken(buff.drain(..).collect())?))
-        } else {
-            Ok(())
+            t.push(parseToken(std::mem::take(buff))?);
         }
+        Ok(())
     };
 
-    for char in input.chars() {
+    let mut chars = input.chars().peekable();
+
+    while let Some(char) = chars.next() {
         match (state, char) {
-            (_, '+' | '-' | '*' | '^' | '(' | ')') => {
+            // A percent sign right after a number (`15%`) makes it a percentage, unless it's directly followed
+            // by something which could be the right side of a modulo (`15%4`). With a space in front, it's
+            // always a modulo (`15 % 4`).
+            (State::ParsingNumber, '%')
+                if !chars.peek().is_some_and(|next| {
+                    next.is_ascii_alphanumeric() || matches!(next, '_' | '(')
+                }) =>
+            {
+                let number = std::mem::take(&mut buffer);
+                state = State::Whitespace;
+
+                match Number::parse(&number).and_then(|num| num.percent()) {
+                    Some(percentage) => tokens.push(Token::Number(percentage)),
+                    None => return Err(ParsingErr::UnrecognizedExpression(number + "%")),
+                }
+            }
+
+            (_, '"') => {
                 createToken(&mut tokens, &mut buffer)?;
+                state = State::Whitespace;
 
-                tokens.push(Token::Operator(char));
+                tokens.push(Token::Str(readString(&mut chars)?));
             }
 
-            (State::ParsingVariable, 'a'..='z' | 'A'..='Z') => {
+            // A name directly followed by a bracket is a function call, like `max(a, b)`. With a space in between,
+            // it's a variable followed by a bracket, which isn't valid.
+            (State::ParsingVariable, '(') => {
+                tokens.push(Token::Function(std::mem::take(&mut buffer)));
+                state = State::Whitespace;
+            }
+
+            (
+                _,
+                '+' | '-' | '*' | '/' | '%' | '^' | '(' | ')' | '<' | '>' | '=' | '!' | '&' | '|'
+                | ',',
+            ) => {
+                createToken(&mut tokens, &mut buffer)?;
+                state = State::Whitespace;
+
+                let operator = matchOperator(char, chars.peek().copied())
+                    .ok_or(ParsingErr::UnrecognizedExpression(char.to_string()))?;
+                // The second character of a two character operator is already a part of it.
+                if operator.len() == 2 {
+                    chars.next();
+                }
+
+                tokens.push(Token::Operator(operator));
+            }
+
+            // Dice, like `2d6`. The number of sides has to come right after the `d`.
+            (State::ParsingNumber, 'd')
+                if chars.peek().is_some_and(|next| next.is_ascii_digit()) =>
+            {
                 buffer.push(char);
             }
-            (_, 'a'..='z' | 'A'..='Z') => {
+            (State::ParsingVariable, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_') => {
+                buffer.push(char);
+            }
+            // A dot in a name separates an entity from its field (`hero.str`), so there has to be a name after it.
+            (State::ParsingVariable, '.')
+                if chars
+                    .peek()
+                    .is_some_and(|next| next.is_ascii_alphabetic() || *next == '_') =>
+            {
+                buffer.push(char);
+            }
+            (_, 'a'..='z' | 'A'..='Z' | '_') => {
                 createToken(&mut tokens, &mut buffer)?;
 
                 state = State::ParsingVariable;
                 buffer.push(char);
             }
 
-            (State::ParsingNumber, '0'..='9') => {
+            (State::ParsingNumber, '0'..='9' | '.') => {
                 buffer.push(char);
             }
             (_, '0'..='9') => {
@@ -381,12 +1180,72 @@ fn tokenizeExpression(input: String) -> Result<Vec<Token>, ParsingErr> {
     Ok(tokens)
 }
 
+/// All of the operators, with the longer ones first, so that `<=` isn't read as `<` followed by `=`.
+/// A single `=`, `&` or `|` isn't an operator on its own.
+const OPERATORS: [&str; 18] = [
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "^", "(", ")", "<", ">", "!", ",",
+];
+
+/// Returns the operator which starts with the given character, taking the one after it into account.
+fn matchOperator(first: char, second: Option<char>) -> Option<&'static str> {
+    let text: String = [Some(first), second].into_iter().flatten().collect();
+
+    OPERATORS
+        .into_iter()
+        .find(|operator| text.starts_with(operator))
+}
+
+/// Reads the rest of a string, after its opening quote, up to and including the closing one.
+/// A backslash means that the character after it is a part of the string, even if it's a quote.
+fn readString(chars: &mut impl Iterator<Item = char>) -> Result<String, ParsingErr> {
+    let mut text = String::new();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '"' => return Ok(text),
+            '\\' => text.push(chars.next().ok_or(ParsingErr::StringLeftOpen)?),
+            other => text.push(other),
+        }
+    }
+
+    Err(ParsingErr::StringLeftOpen)
+}
+
 fn parseToken(input: String) -> Result<Token, ParsingErr> {
-    if let Ok(num) = input.parse::<i32>() {
-        Ok(Token::Number(num))
-    } else if input.chars().all(|c| c.is_ascii_alphabetic()) {
+    let dice = input
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| input.split_once('d'))
+        .flatten();
+
+    if let Some((count, sides)) = dice {
+        // Both sides have to be whole numbers, and there has to be at least one die with at least one side.
+        match (count.parse::<u32>(), sides.parse::<u32>()) {
+            (Ok(count @ 1..=MAX_DICE), Ok(sides @ 1..)) => Ok(Token::Dice(count, sides)),
+            _ => Err(ParsingErr::UnrecognizedExpression(input)),
+        }
+    } else if input.starts_with(|c: char| c.is_ascii_digit()) {
+        // Numbers of any size are parsed, it's up to the interpreter to decide whether they're too big.
+        Number::parse(&input)
+            .map(Token::Number)
+            .ok_or(ParsingErr::UnrecognizedExpression(input))
+    } else if isIdentifier(&input) {
         Ok(Token::Variable(input))
     } else {
         Err(ParsingErr::UnrecognizedExpression(input))
     }
 }
+
+/// Variable names start with a letter or an underscore, and can contain digits after that (`max_hp`, `skill2`).
+/// The fields of entities are written with dots between the names (`hero.str`, `hero.pet.hp`).
+fn isIdentifier(input: &str) -> bool {
+    input.split('.').all(|part| {
+        let mut chars = part.chars();
+
+        match chars.next() {
+            Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        }
+    })
+}
@@ -2,8 +2,13 @@
 // thinks that the imports that ARE NECESSARY are unused. No idea why.
 #![allow(unused_imports)]
 
+use crate::types::curve::{Curve, Thresholds};
+use crate::types::effect::TimeUnit;
+use crate::types::engine::{NumberMode, Refill, Rounding, Storage};
 use crate::types::error::ParsingErr;
-use crate::types::{BiOperation, Command, Content, Expression};
+use crate::types::number::Number;
+use crate::types::value::Value;
+use crate::types::{BiOperation, Command, Content, Expression, Format, Setting, UnOperation};
 
 use super::{parseCommand, parseExpression, parseFile, parseToken, tokenizeExpression, Token};
 
@@ -13,8 +18,8 @@ fn expression() {
         parseExpression("1 + 2".to_string()),
         Ok(Expression::Binary(
             BiOperation::Add,
-            Box::new(Expression::Value(1)),
-            Box::new(Expression::Value(2))
+            Box::new(Expression::Value(Number::Int(1))),
+            Box::new(Expression::Value(Number::Int(2)))
         ))
     );
 
@@ -22,14 +27,476 @@ fn expression() {
         parseExpression("1 + 2 * 3".to_string()),
         Ok(Expression::Binary(
             BiOperation::Add,
-            Box::new(Expression::Value(1)),
+            Box::new(Expression::Value(Number::Int(1))),
             Box::new(Expression::Binary(
                 BiOperation::Multiply,
-                Box::new(Expression::Value(2)),
-                Box::new(Expression::Value(3))
+                Box::new(Expression::Value(Number::Int(2))),
+                Box::new(Expression::Value(Number::Int(3)))
             ))
         ))
     );
+
+    // Multiplication binds more strongly on both sides of an addition
+    assert_eq!(
+        parseExpression("1 * 2 + 3 * 4 - 5".to_string()),
+        Ok(binary(
+            BiOperation::Subtract,
+            binary(
+                BiOperation::Add,
+                binary(BiOperation::Multiply, value(1), value(2)),
+                binary(BiOperation::Multiply, value(3), value(4))
+            ),
+            value(5)
+        ))
+    );
+
+    // Exponentiation is right-associative
+    assert_eq!(
+        parseExpression("2 ^ 3 ^ 2".to_string()),
+        Ok(binary(
+            BiOperation::Exponentiate,
+            value(2),
+            binary(BiOperation::Exponentiate, value(3), value(2))
+        ))
+    );
+
+    // Brackets, including nested and redundant ones
+    assert_eq!(
+        parseExpression("((1 + 2)) * (3 - (4))".to_string()),
+        Ok(binary(
+            BiOperation::Multiply,
+            binary(BiOperation::Add, value(1), value(2)),
+            binary(BiOperation::Subtract, value(3), value(4))
+        ))
+    );
+
+    // Unary minus binds more strongly than multiplication, but less than exponentiation
+    assert_eq!(
+        parseExpression("-2 ^ 2 * -a".to_string()),
+        Ok(binary(
+            BiOperation::Multiply,
+            Expression::Unary(
+                UnOperation::Minus,
+                Box::new(binary(BiOperation::Exponentiate, value(2), value(2)))
+            ),
+            Expression::Unary(
+                UnOperation::Minus,
+                Box::new(Expression::Variable("a".to_string()))
+            )
+        ))
+    );
+    assert_eq!(
+        parseExpression("2 ^ -1".to_string()),
+        Ok(binary(BiOperation::Exponentiate, value(2), value(-1)))
+    );
+
+    for wrong in [
+        "", "(", "(1 + 2", "1 + 2)", ")(", "1 +", "* 2", "1 2", "a (b)",
+    ] {
+        assert_eq!(
+            parseExpression(wrong.to_string()),
+            Err(ParsingErr::ExpressionParsing),
+            "'{wrong}' should not parse"
+        );
+    }
+
+    // Deep nesting is an error, not a stack overflow
+    assert_eq!(
+        parseExpression("(".repeat(10000) + "1" + &")".repeat(10000)),
+        Err(ParsingErr::ExpressionTooDeep)
+    );
+    assert_eq!(
+        parseExpression("-".repeat(10000) + "1"),
+        Err(ParsingErr::ExpressionTooDeep)
+    );
+}
+
+fn value(val: i64) -> Expression {
+    Expression::Value(Number::Int(val))
+}
+
+fn binary(operation: BiOperation, lhand: Expression, rhand: Expression) -> Expression {
+    Expression::Binary(operation, Box::new(lhand), Box::new(rhand))
+}
+
+/// A tiny pseudo-random generator, so the property tests are reproducible without any extra crates.
+struct TestRng(u64);
+
+impl TestRng {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
+
+/// Generates a random expression tree out of small numbers.
+fn randomExpression(rng: &mut TestRng, depth: u32) -> Expression {
+    if depth == 0 || rng.below(4) == 0 {
+        return value(rng.below(10) as i64);
+    }
+
+    match rng.below(16) {
+        0 => Expression::Unary(
+            UnOperation::Minus,
+            Box::new(randomExpression(rng, depth - 1)),
+        ),
+        1 => Expression::Unary(UnOperation::Not, Box::new(randomExpression(rng, depth - 1))),
+        choice => binary(
+            [
+                BiOperation::Add,
+                BiOperation::Subtract,
+                BiOperation::Multiply,
+                BiOperation::Divide,
+                BiOperation::Modulo,
+                BiOperation::Exponentiate,
+                BiOperation::Less,
+                BiOperation::LessOrEqual,
+                BiOperation::Greater,
+                BiOperation::GreaterOrEqual,
+                BiOperation::Equal,
+                BiOperation::NotEqual,
+                BiOperation::And,
+                BiOperation::Or,
+            ][choice as usize - 2],
+            randomExpression(rng, depth - 1),
+            randomExpression(rng, depth - 1),
+        ),
+    }
+}
+
+/// Precedence and associativity of the usual math notation, which the parser is tested against.
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Binary(BiOperation::Or, _, _) => 1,
+        Expression::Binary(BiOperation::And, _, _) => 2,
+        Expression::Binary(BiOperation::Equal | BiOperation::NotEqual, _, _) => 3,
+        Expression::Binary(
+            BiOperation::Less
+            | BiOperation::LessOrEqual
+            | BiOperation::Greater
+            | BiOperation::GreaterOrEqual,
+            _,
+            _,
+        ) => 4,
+        Expression::Binary(BiOperation::Add | BiOperation::Subtract, _, _) => 5,
+        Expression::Binary(
+            BiOperation::Multiply | BiOperation::Divide | BiOperation::Modulo,
+            _,
+            _,
+        ) => 6,
+        Expression::Unary(_, _) => 7,
+        Expression::Binary(BiOperation::Exponentiate, _, _) => 8,
+        _ => 9,
+    }
+}
+
+/// Writes the expression down with as few brackets as possible, so the parser has to get precedence right.
+fn render(expression: &Expression) -> String {
+    let bracketed = |expr: &Expression, needed: bool| {
+        if needed {
+            format!("({})", render(expr))
+        } else {
+            render(expr)
+        }
+    };
+
+    match expression {
+        Expression::Value(val) => val.to_string(),
+        Expression::Variable(var) => var.to_owned(),
+        Expression::Str(_) | Expression::Call(_, _) | Expression::Dice(_, _) => {
+            expression.to_string()
+        }
+        Expression::Unary(operation, expr) => {
+            format!(
+                "{operation}{}",
+                bracketed(expr, precedence(expr) < precedence(expression))
+            )
+        }
+        Expression::Binary(operation, lhand, rhand) => {
+            let own = precedence(expression);
+            let right_associative = *operation == BiOperation::Exponentiate;
+
+            format!(
+                "{} {operation} {}",
+                bracketed(
+                    lhand,
+                    precedence(lhand) < own || (right_associative && precedence(lhand) == own)
+                ),
+                bracketed(
+                    rhand,
+                    precedence(rhand) < own || (!right_associative && precedence(rhand) == own)
+                )
+            )
+        }
+    }
+}
+
+/// The reference evaluator. `None` means that the expression can't be computed (overflow or negative exponent).
+fn referenceEvaluate(expression: &Expression) -> Option<i64> {
+    match expression {
+        Expression::Value(Number::Int(val)) => Some(*val),
+        Expression::Value(_)
+        | Expression::Str(_)
+        | Expression::Variable(_)
+        | Expression::Call(_, _)
+        | Expression::Dice(_, _) => None,
+        Expression::Unary(UnOperation::Minus, expr) => referenceEvaluate(expr)?.checked_neg(),
+        Expression::Unary(UnOperation::Not, expr) => Some((referenceEvaluate(expr)? == 0) as i64),
+        Expression::Binary(BiOperation::And, lhand, rhand) => {
+            Some((referenceEvaluate(lhand)? != 0 && referenceEvaluate(rhand)? != 0) as i64)
+        }
+        Expression::Binary(BiOperation::Or, lhand, rhand) => {
+            Some((referenceEvaluate(lhand)? != 0 || referenceEvaluate(rhand)? != 0) as i64)
+        }
+        Expression::Binary(operation, lhand, rhand) => {
+            let lhand = referenceEvaluate(lhand)?;
+            let rhand = referenceEvaluate(rhand)?;
+            match operation {
+                BiOperation::Add => lhand.checked_add(rhand),
+                BiOperation::Subtract => lhand.checked_sub(rhand),
+                BiOperation::Multiply => lhand.checked_mul(rhand),
+                BiOperation::Exponentiate => lhand.checked_pow(u32::try_from(rhand).ok()?),
+                BiOperation::Less => Some((lhand < rhand) as i64),
+                BiOperation::LessOrEqual => Some((lhand <= rhand) as i64),
+                BiOperation::Greater => Some((lhand > rhand) as i64),
+                BiOperation::GreaterOrEqual => Some((lhand >= rhand) as i64),
+                BiOperation::Equal => Some((lhand == rhand) as i64),
+                BiOperation::NotEqual => Some((lhand != rhand) as i64),
+                BiOperation::And | BiOperation::Or => unreachable!("Handled above"),
+                // Division rounds down, as that's the default of the interpreter.
+                BiOperation::Divide | BiOperation::Modulo => {
+                    let truncated = lhand.checked_div(rhand)?;
+                    let floored = if lhand % rhand != 0 && (lhand < 0) != (rhand < 0) {
+                        truncated - 1
+                    } else {
+                        truncated
+                    };
+
+                    if *operation == BiOperation::Divide {
+                        Some(floored)
+                    } else {
+                        Some(lhand - rhand * floored)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Folds the minus in front of numbers the same way the parser does.
+fn normalize(expression: Expression) -> Expression {
+    match expression {
+        Expression::Unary(UnOperation::Minus, expr) => match normalize(*expr) {
+            Expression::Value(val) => match val.neg() {
+                Some(negative) => Expression::Value(negative),
+                None => Expression::Unary(UnOperation::Minus, Box::new(Expression::Value(val))),
+            },
+            other => Expression::Unary(UnOperation::Minus, Box::new(other)),
+        },
+        Expression::Unary(operation, expr) => {
+            Expression::Unary(operation, Box::new(normalize(*expr)))
+        }
+        Expression::Binary(operation, lhand, rhand) => {
+            binary(operation, normalize(*lhand), normalize(*rhand))
+        }
+        other => other,
+    }
+}
+
+#[test]
+fn expressionProperties() {
+    let mut rng = TestRng(0x5eed);
+
+    for _ in 0..2000 {
+        let generated = randomExpression(&mut rng, 5);
+
+        // Both the minimal and the fully bracketed form should parse back into the same tree.
+        for text in [render(&generated), format!("{generated}")] {
+            let parsed = parseExpression(text.clone())
+                .unwrap_or_else(|err| panic!("'{text}' failed to parse: {err}"));
+
+            assert_eq!(
+                parsed,
+                normalize(generated.clone()),
+                "'{text}' parsed wrong"
+            );
+            // The interpreter has to agree with the reference on what the parsed tree comes out to, with its errors
+            // (like overflows or dividing by zero) being the cases where the reference gives up.
+            let evaluated = crate::interpreter::evaluate(&Storage::new(), &parsed)
+                .ok()
+                .and_then(|value| match value {
+                    Value::Number(number) => number.toWhole(),
+                    _ => None,
+                });
+            assert_eq!(
+                evaluated,
+                referenceEvaluate(&generated),
+                "'{text}' evaluated wrong"
+            );
+        }
+    }
+}
+
+#[test]
+fn expressionNeverPanics() {
+    let mut rng = TestRng(0xf022);
+    let alphabet: Vec<char> = "0123456789ab+-*/%^()<>=!&|,  ".chars().collect();
+
+    for _ in 0..5000 {
+        let length = rng.below(24);
+        let input: String = (0..length)
+            .map(|_| alphabet[rng.below(alphabet.len() as u64) as usize])
+            .collect();
+
+        // Whatever the result is, it just can't panic.
+        let _ = parseExpression(input);
+    }
+}
+
+#[test]
+fn writeExpression() {
+    assert_eq!(
+        parseCommand("write level * 2 + 5".to_string()),
+        Ok(vec![Command::Write(
+            Expression::Binary(
+                BiOperation::Add,
+                Box::new(Expression::Binary(
+                    BiOperation::Multiply,
+                    Box::new(Expression::Variable("level".to_string())),
+                    Box::new(Expression::Value(Number::Int(2)))
+                )),
+                Box::new(Expression::Value(Number::Int(5)))
+            ),
+            Format::Plain
+        )])
+    );
+
+    assert_eq!(
+        parseCommand("write".to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
+}
+
+#[test]
+fn decimals() {
+    let decimal = |text: &str| Expression::Value(Number::parse(text).expect("Not a number"));
+
+    assert_eq!(parseExpression("1.25".to_string()), Ok(decimal("1.25")));
+    assert_eq!(
+        parseExpression("-0.5".to_string()),
+        Ok(Expression::Value(
+            Number::parse("0.5")
+                .and_then(|n| n.neg())
+                .expect("Not a number")
+        ))
+    );
+    assert_eq!(parseExpression("15%".to_string()), Ok(decimal("0.15")));
+    assert_eq!(
+        parseExpression("damage * 15%".to_string()),
+        Ok(binary(
+            BiOperation::Multiply,
+            Expression::Variable("damage".to_string()),
+            decimal("0.15")
+        ))
+    );
+    assert_eq!(
+        parseExpression("(10%) + 1".to_string()),
+        Ok(binary(BiOperation::Add, decimal("0.1"), value(1)))
+    );
+
+    // A percent sign followed by a number or a variable is still a modulo.
+    assert_eq!(
+        parseExpression("15%4".to_string()),
+        Ok(binary(BiOperation::Modulo, value(15), value(4)))
+    );
+    assert_eq!(
+        parseExpression("15 % level".to_string()),
+        Ok(binary(
+            BiOperation::Modulo,
+            value(15),
+            Expression::Variable("level".to_string())
+        ))
+    );
+
+    assert_eq!(
+        parseExpression("1.2.3".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("1.2.3".to_string()))
+    );
+
+    assert_eq!(
+        parseCommand("write crit as decimals 2".to_string()),
+        Ok(vec![Command::Write(
+            Expression::Variable("crit".to_string()),
+            Format::Decimals(2)
+        )])
+    );
+    assert_eq!(
+        parseCommand("write crit as percent".to_string()),
+        Err(ParsingErr::UnrecognizedFormat("percent".to_string()))
+    );
+    assert_eq!(
+        parseCommand("config precision 6".to_string()),
+        Ok(vec![Command::Config(Setting::Precision(6))])
+    );
+    assert_eq!(
+        parseCommand("config precision 40".to_string()),
+        Err(ParsingErr::UnrecognizedSetting("precision 40".to_string()))
+    );
+}
+
+#[test]
+fn conditions() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseExpression("hp <= 0 || level >= 10 && mana > 5".to_string()),
+        Ok(binary(
+            BiOperation::Or,
+            binary(BiOperation::LessOrEqual, variable("hp"), value(0)),
+            binary(
+                BiOperation::And,
+                binary(BiOperation::GreaterOrEqual, variable("level"), value(10)),
+                binary(BiOperation::Greater, variable("mana"), value(5))
+            )
+        ))
+    );
+    assert_eq!(
+        parseExpression("!dead && hp+5<max_hp==1".to_string()),
+        Ok(binary(
+            BiOperation::And,
+            Expression::Unary(UnOperation::Not, Box::new(variable("dead"))),
+            binary(
+                BiOperation::Equal,
+                binary(
+                    BiOperation::Less,
+                    binary(BiOperation::Add, variable("hp"), value(5)),
+                    variable("max_hp")
+                ),
+                value(1)
+            )
+        ))
+    );
+    assert_eq!(
+        parseExpression("a != -1".to_string()),
+        Ok(binary(BiOperation::NotEqual, variable("a"), value(-1)))
+    );
+
+    // A single `=`, `&` or `|` isn't an operator.
+    assert_eq!(
+        parseExpression("a = 1".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("=".to_string()))
+    );
+    assert_eq!(
+        parseExpression("a & b".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("&".to_string()))
+    );
+    assert_eq!(
+        parseExpression("a < ".to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
 }
 
 #[test]
@@ -37,34 +504,43 @@ fn tokenizer() {
     assert_eq!(
         tokenizeExpression("1+2".to_string()),
         Ok(vec![
-            Token::Number(1),
-            Token::Operator('+'),
-            Token::Number(2)
+            Token::Number(Number::Int(1)),
+            Token::Operator("+"),
+            Token::Number(Number::Int(2))
         ])
     );
     assert_eq!(
         tokenizeExpression("1  +    2".to_string()),
         Ok(vec![
-            Token::Number(1),
-            Token::Operator('+'),
-            Token::Number(2)
+            Token::Number(Number::Int(1)),
+            Token::Operator("+"),
+            Token::Number(Number::Int(2))
+        ])
+    );
+
+    assert_eq!(
+        tokenizeExpression("max_hp - hp2".to_string()),
+        Ok(vec![
+            Token::Variable("max_hp".to_string()),
+            Token::Operator("-"),
+            Token::Variable("hp2".to_string()),
         ])
     );
 
     assert_eq!(
         tokenizeExpression("1*2 + (3 1 - test))".to_string()),
         Ok(vec![
-            Token::Number(1),
-            Token::Operator('*'),
-            Token::Number(2),
-            Token::Operator('+'),
-            Token::Operator('('),
-            Token::Number(3),
-            Token::Number(1),
-            Token::Operator('-'),
+            Token::Number(Number::Int(1)),
+            Token::Operator("*"),
+            Token::Number(Number::Int(2)),
+            Token::Operator("+"),
+            Token::Operator("("),
+            Token::Number(Number::Int(3)),
+            Token::Number(Number::Int(1)),
+            Token::Operator("-"),
             Token::Variable("test".to_string()),
-            Token::Operator(')'),
-            Token::Operator(')'),
+            Token::Operator(")"),
+            Token::Operator(")"),
         ])
     );
 }
@@ -83,23 +559,95 @@ fn command() {
 
     assert_eq!(
         parseCommand("let it be...".to_string()),
-        Err(ParsingErr::NumberParsing(
-            "be...".parse::<i32>().expect_err("")
-        ))
+        Err(ParsingErr::ExpressionParsing)
+    );
+
+    // Numbers of any size are parsed, it's the interpreter which decides if they're too big
+    assert_eq!(
+        parseCommand("let big 99999999999; let bigger 99999999999999999999".to_string()),
+        Ok(vec![
+            Command::Let("big".to_string(), value(99999999999)),
+            Command::Let(
+                "bigger".to_string(),
+                Expression::Value(Number::parse("99999999999999999999").expect(""))
+            )
+        ])
     );
 
     assert_eq!(
         parseCommand("subtract zero -1".to_string()),
-        Ok(vec![Command::Subtract("zero".to_string(), -1)])
+        Ok(vec![Command::Subtract(
+            "zero".to_string(),
+            Expression::Value(Number::Int(-1))
+        )])
     );
 
     assert_eq!(
-        parseCommand("subtract zero -1; let a 10".to_string()),
+        parseCommand("subtract zero 1; let a 10".to_string()),
         Ok(vec![
-            Command::Subtract("zero".to_string(), -1),
-            Command::Let("a".to_string(), 10)
+            Command::Subtract("zero".to_string(), Expression::Value(Number::Int(1))),
+            Command::Let("a".to_string(), Expression::Value(Number::Int(10)))
         ])
     );
+
+    assert_eq!(
+        parseCommand("add strength level * 2; set hp max_hp".to_string()),
+        Ok(vec![
+            Command::Add(
+                "strength".to_string(),
+                Expression::Binary(
+                    BiOperation::Multiply,
+                    Box::new(Expression::Variable("level".to_string())),
+                    Box::new(Expression::Value(Number::Int(2)))
+                )
+            ),
+            Command::Set("hp".to_string(), Expression::Variable("max_hp".to_string()))
+        ])
+    );
+}
+
+#[test]
+fn derive() {
+    assert_eq!(
+        parseCommand("derive strength = base_str + level".to_string()),
+        Ok(vec![Command::Derive(
+            "strength".to_string(),
+            Expression::Binary(
+                BiOperation::Add,
+                Box::new(Expression::Variable("base_str".to_string())),
+                Box::new(Expression::Variable("level".to_string()))
+            )
+        )])
+    );
+
+    assert_eq!(
+        parseCommand("derive strength base_str".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+
+    assert_eq!(
+        parseCommand("derive strength is base_str".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("is".to_string()))
+    );
+}
+
+#[test]
+fn config() {
+    assert_eq!(
+        parseCommand(
+            "config rounding truncate; config rounding round; config numbers big".to_string()
+        ),
+        Ok(vec![
+            Command::Config(Setting::Rounding(Rounding::Truncate)),
+            Command::Config(Setting::Rounding(Rounding::Round)),
+            Command::Config(Setting::Numbers(NumberMode::Big))
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("config rounding up".to_string()),
+        Err(ParsingErr::UnrecognizedSetting("rounding up".to_string()))
+    );
 }
 
 #[test]
@@ -114,11 +662,848 @@ fn wholeText() {
         parseFile(goodInput),
         Ok(vec![
             Content::Text("This is text\n".to_string()),
-            Content::Command(Command::Let("variable".to_string(), -1)),
+            Content::Command(Command::Let(
+                "variable".to_string(),
+                Expression::Value(Number::Int(-1))
+            )),
             Content::Text("More text\n".to_string()),
-            Content::Command(Command::Add("variable".to_string(), 2)),
+            Content::Command(Command::Add(
+                "variable".to_string(),
+                Expression::Value(Number::Int(2))
+            )),
             Content::Text("Variable is ".to_string()),
-            Content::Command(Command::Write("variable".to_string()))
+            Content::Command(Command::Write(
+                Expression::Variable("variable".to_string()),
+                Format::Plain
+            ))
+        ])
+    );
+}
+
+#[test]
+fn blocks() {
+    let text = |t: &str| Content::Text(t.to_string());
+    let condition = |t: &str| parseExpression(t.to_string()).expect("Invalid expression");
+
+    assert_eq!(
+        parseFile(
+            "A$if int >= 20$[Skill Unlocked: Fireball]$elseif int >= 10$Almost$else$Nothing$end$B"
+                .to_string()
+        ),
+        Ok(vec![
+            text("A"),
+            Content::Conditional(
+                vec![
+                    (
+                        condition("int >= 20"),
+                        vec![text("[Skill Unlocked: Fireball]")]
+                    ),
+                    (condition("int >= 10"), vec![text("Almost")]),
+                ],
+                vec![text("Nothing")]
+            ),
+            text("B"),
+        ])
+    );
+
+    // Blocks can be nested, and can hold commands too. The empty texts are the nothing between the sections.
+    assert_eq!(
+        parseFile("$if a$$if b; add c 1; else if c$x$end$$end$".to_string()),
+        Ok(vec![
+            text(""),
+            Content::Conditional(
+                vec![(
+                    condition("a"),
+                    vec![
+                        text(""),
+                        Content::Conditional(
+                            vec![
+                                (
+                                    condition("b"),
+                                    vec![Content::Command(Command::Add("c".to_string(), value(1)))]
+                                ),
+                                (condition("c"), vec![text("x")]),
+                            ],
+                            vec![]
+                        ),
+                        text("")
+                    ]
+                )],
+                vec![]
+            )
+        ])
+    );
+
+    assert_eq!(
+        parseFile("$if a$text".to_string()),
+        Err(ParsingErr::BlockLeftOpen)
+    );
+
+    // Deep nesting is an error when it's parsed, rather than a stack overflow when it's run
+    assert_eq!(
+        parseFile("$if 1$".repeat(10000) + &"$end$".repeat(10000)),
+        Err(ParsingErr::BlockTooDeep)
+    );
+    assert_eq!(
+        parseFile("$repeat 1$".repeat(3000) + &"$end$".repeat(3000)),
+        Err(ParsingErr::BlockTooDeep)
+    );
+    let deepest = parseFile("$repeat 1$".repeat(64) + "deep" + &"$end$".repeat(64))
+        .expect("The limit itself is fine");
+    assert_eq!(
+        crate::interpreter::run(&mut Storage::new(), deepest),
+        Ok("deep".to_string())
+    );
+    assert_eq!(
+        parseFile("$if a$$end$$end$".to_string()),
+        Err(ParsingErr::UnexpectedBlockMarker("end".to_string()))
+    );
+    assert_eq!(
+        parseFile("text$else$".to_string()),
+        Err(ParsingErr::UnexpectedBlockMarker("else".to_string()))
+    );
+    assert_eq!(
+        parseFile("$if a$$else$$elseif b$$end$".to_string()),
+        Err(ParsingErr::UnexpectedBlockMarker("elseif".to_string()))
+    );
+    assert_eq!(
+        parseCommand("end now".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("if".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn loops() {
+    let text = |t: &str| Content::Text(t.to_string());
+    let expression = |t: &str| parseExpression(t.to_string()).expect("Invalid expression");
+
+    assert_eq!(
+        parseFile("$repeat 12 as day$Day $write day$$end$".to_string()),
+        Ok(vec![
+            text(""),
+            Content::Repeat(
+                value(12),
+                Some("day".to_string()),
+                vec![
+                    text("Day "),
+                    Content::Command(Command::Write(expression("day"), Format::Plain)),
+                    text(""),
+                ]
+            ),
+        ])
+    );
+    assert_eq!(
+        parseFile("$while hp < max_hp; add hp 10; end$".to_string()),
+        Ok(vec![
+            text(""),
+            Content::While(
+                expression("hp < max_hp"),
+                None,
+                vec![Content::Command(Command::Add("hp".to_string(), value(10)))]
+            ),
+        ])
+    );
+
+    // An `else` belongs to an `if`, not to a loop.
+    assert_eq!(
+        parseFile("$repeat 2; else; end$".to_string()),
+        Err(ParsingErr::UnexpectedBlockMarker("else".to_string()))
+    );
+    assert_eq!(
+        parseFile("$while 1$".to_string()),
+        Err(ParsingErr::BlockLeftOpen)
+    );
+    assert_eq!(
+        parseCommand("repeat 3 as 2nd".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2nd".to_string()))
+    );
+    assert_eq!(
+        parseCommand("config iterations 500".to_string()),
+        Ok(vec![Command::Config(Setting::Iterations(500))])
+    );
+}
+
+#[test]
+fn functionCalls() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseExpression("clamp(hp + 20, 0, max_hp)".to_string()),
+        Ok(Expression::Call(
+            "clamp".to_string(),
+            vec![
+                binary(BiOperation::Add, variable("hp"), value(20)),
+                value(0),
+                variable("max_hp")
+            ]
+        ))
+    );
+    assert_eq!(
+        parseExpression("2 * max(a, min(b, 3)) ^ 2".to_string()),
+        Ok(binary(
+            BiOperation::Multiply,
+            value(2),
+            binary(
+                BiOperation::Exponentiate,
+                Expression::Call(
+                    "max".to_string(),
+                    vec![
+                        variable("a"),
+                        Expression::Call("min".to_string(), vec![variable("b"), value(3)])
+                    ]
+                ),
+                value(2)
+            )
+        ))
+    );
+    assert_eq!(
+        parseExpression("roll()".to_string()),
+        Ok(Expression::Call("roll".to_string(), vec![]))
+    );
+
+    // The calls are written back the same way they're written.
+    assert_eq!(
+        parseExpression("abs(-3)".to_string()).map(|expr| expr.to_string()),
+        Ok("abs((-3))".to_string())
+    );
+
+    for wrong in [
+        "max(1,)", "max(1 2)", "max(1, 2", "(1, 2)", "1, 2", "max(,)",
+    ] {
+        assert_eq!(
+            parseExpression(wrong.to_string()),
+            Err(ParsingErr::ExpressionParsing),
+            "'{wrong}' shouldn't parse"
+        );
+    }
+}
+
+#[test]
+fn strings() {
+    let string = |text: &str| Expression::Str(text.to_string());
+
+    assert_eq!(
+        parseCommand(r#"let class "Spellblade""#.to_string()),
+        Ok(vec![Command::Let(
+            "class".to_string(),
+            string("Spellblade")
+        )])
+    );
+
+    // Separators inside of a string are a part of it.
+    assert_eq!(
+        parseCommand(
+            r#"let title "The  Unkillable;  Slayer" ; set title title + " of \"Gods\"""#
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Let("title".to_string(), string("The  Unkillable;  Slayer")),
+            Command::Set(
+                "title".to_string(),
+                binary(
+                    BiOperation::Add,
+                    Expression::Variable("title".to_string()),
+                    string(" of \"Gods\"")
+                )
+            ),
+        ])
+    );
+    assert_eq!(
+        parseExpression(r#"class == "Mage\\""#.to_string()),
+        Ok(binary(
+            BiOperation::Equal,
+            Expression::Variable("class".to_string()),
+            string("Mage\\")
+        ))
+    );
+
+    // Strings are written back so that they parse into the same thing.
+    let tricky = string("say \"hi\" \\ bye");
+    assert_eq!(parseExpression(tricky.to_string()), Ok(tricky));
+
+    assert_eq!(
+        parseCommand(r#"let class "Spellblade"#.to_string()),
+        Err(ParsingErr::StringLeftOpen)
+    );
+    assert_eq!(
+        parseExpression(r#""a" "b""#.to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
+}
+
+#[test]
+fn entities() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseCommand("entity hero; let hero.str 10; fields hero; delete hero.pet".to_string()),
+        Ok(vec![
+            Command::Entity("hero".to_string()),
+            Command::Let("hero.str".to_string(), value(10)),
+            Command::Fields("hero".to_string()),
+            Command::Delete("hero.pet".to_string()),
+        ])
+    );
+    assert_eq!(
+        parseExpression("hero.str * 2 + hero.pet._hp".to_string()),
+        Ok(binary(
+            BiOperation::Add,
+            binary(BiOperation::Multiply, variable("hero.str"), value(2)),
+            variable("hero.pet._hp")
+        ))
+    );
+    // A field is a name of its own, so a number can't follow the dot.
+    assert_eq!(
+        parseExpression("hero.2".to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
+
+    assert_eq!(
+        parseCommand("entity hero pet".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("entity hero.".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("hero.".to_string()))
+    );
+}
+
+#[test]
+fn classes() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+    let field = |name: &str, formula: Expression| (name.to_string(), formula);
+
+    // A class can span several lines, and the commas of a function call aren't mistaken for the ones
+    // between the fields.
+    assert_eq!(
+        parseCommand(
+            "class Warrior {\n  str: 10 + level * 2,\n  vit: max(8, level),\n}\nnew hero Warrior { vit: 20 }; new guard Warrior"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Class(
+                "Warrior".to_string(),
+                vec![
+                    field(
+                        "str",
+                        binary(
+                            BiOperation::Add,
+                            value(10),
+                            binary(BiOperation::Multiply, variable("level"), value(2))
+                        )
+                    ),
+                    field(
+                        "vit",
+                        Expression::Call("max".to_string(), vec![value(8), variable("level")])
+                    ),
+                ]
+            ),
+            Command::New(
+                "hero".to_string(),
+                "Warrior".to_string(),
+                vec![field("vit", value(20))]
+            ),
+            Command::New("guard".to_string(), "Warrior".to_string(), vec![]),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("class Warrior { str: 1, str: 2 }".to_string()),
+        Err(ParsingErr::DuplicateField("str".to_string()))
+    );
+    assert_eq!(
+        parseCommand("class Warrior { hero.str: 1 }".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("hero.str".to_string()))
+    );
+    assert_eq!(
+        parseCommand("class Warrior { str 1 }".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("str 1".to_string()))
+    );
+    assert_eq!(
+        parseCommand("class Warrior str: 1".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("new hero Warrior str".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("str".to_string()))
+    );
+}
+
+#[test]
+fn lists() {
+    let string = |text: &str| Expression::Str(text.to_string());
+
+    assert_eq!(
+        parseCommand(
+            r#"list inventory; push inventory "Health Potion" x 2 + 1; push inventory "Iron x Sword"; remove inventory "Health" + " Potion""#
+                .to_string()
+        ),
+        Ok(vec![
+            Command::List("inventory".to_string()),
+            Command::Push(
+                "inventory".to_string(),
+                string("Health Potion"),
+                binary(BiOperation::Add, value(2), value(1))
+            ),
+            Command::Push("inventory".to_string(), string("Iron x Sword"), value(1)),
+            Command::Remove(
+                "inventory".to_string(),
+                binary(BiOperation::Add, string("Health"), string(" Potion")),
+                value(1)
+            ),
+        ])
+    );
+    assert_eq!(
+        parseCommand("write inventory as bullets".to_string()),
+        Ok(vec![Command::Write(
+            Expression::Variable("inventory".to_string()),
+            Format::Bullets
+        )])
+    );
+    assert_eq!(
+        parseExpression(r#"contains(inventory, "Iron Sword")"#.to_string()),
+        Ok(Expression::Call(
+            "contains".to_string(),
+            vec![
+                Expression::Variable("inventory".to_string()),
+                string("Iron Sword")
+            ]
+        ))
+    );
+
+    assert_eq!(
+        parseCommand("push inventory".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand(r#"push inventory "Health Potion" x"#.to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
+    assert_eq!(
+        parseCommand("list inventory bag".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn macros() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseFile(
+            "$def levelup(who, amount)$Level up!$add who.level amount$$end$$call levelup(hero, 1 + 1)$$call recap$"
+                .to_string()
+        ),
+        Ok(vec![
+            Content::Text("".to_string()),
+            Content::Macro(
+                "levelup".to_string(),
+                vec!["who".to_string(), "amount".to_string()],
+                vec![
+                    Content::Text("Level up!".to_string()),
+                    Content::Command(Command::Add(
+                        "who.level".to_string(),
+                        variable("amount")
+                    )),
+                    Content::Text("".to_string()),
+                ]
+            ),
+            Content::Text("".to_string()),
+            Content::Command(Command::Call(
+                "levelup".to_string(),
+                vec![
+                    variable("hero"),
+                    binary(BiOperation::Add, value(1), value(1))
+                ]
+            )),
+            Content::Text("".to_string()),
+            Content::Command(Command::Call("recap".to_string(), vec![])),
         ])
     );
+    assert_eq!(
+        parseCommand("config depth 10".to_string()),
+        Ok(vec![Command::Config(Setting::Depth(10))])
+    );
+    assert_eq!(
+        parseCommand("config depth 1000".to_string()),
+        Err(ParsingErr::UnrecognizedSetting("depth 1000".to_string()))
+    );
+
+    assert_eq!(
+        parseFile("$def recap$Nothing happened.".to_string()),
+        Err(ParsingErr::BlockLeftOpen)
+    );
+    assert_eq!(
+        parseCommand("def levelup(who, who)".to_string()),
+        Err(ParsingErr::DuplicateParameter("who".to_string()))
+    );
+    assert_eq!(
+        parseCommand("def levelup(who.level)".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("who.level".to_string()))
+    );
+    assert_eq!(
+        parseCommand("call 1 + 2".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("1 + 2".to_string()))
+    );
+    assert_eq!(
+        parseCommand("call".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn dice() {
+    assert_eq!(
+        parseExpression("2d6+3 * d6".to_string()),
+        Ok(binary(
+            BiOperation::Add,
+            Expression::Dice(2, 6),
+            binary(
+                BiOperation::Multiply,
+                value(3),
+                Expression::Variable("d6".to_string())
+            )
+        ))
+    );
+    assert_eq!(
+        parseExpression("random(1, 1d20)".to_string()),
+        Ok(Expression::Call(
+            "random".to_string(),
+            vec![value(1), Expression::Dice(1, 20)]
+        ))
+    );
+    assert_eq!(
+        parseCommand("seed 1234; roll 2d6 + 3".to_string()),
+        Ok(vec![
+            Command::Seed(1234),
+            Command::Roll(binary(BiOperation::Add, Expression::Dice(2, 6), value(3))),
+        ])
+    );
+
+    for wrong in ["0d6", "2d0", "2000d6", "2d6.5", "1.5d6", "2d99999999999"] {
+        assert_eq!(
+            parseExpression(wrong.to_string()),
+            Err(ParsingErr::UnrecognizedExpression(wrong.to_string())),
+            "'{wrong}' shouldn't parse"
+        );
+    }
+    assert_eq!(
+        parseExpression("2d".to_string()),
+        Err(ParsingErr::ExpressionParsing)
+    );
+    assert_eq!(
+        parseCommand("seed -5".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("-5".to_string()))
+    );
+}
+
+#[test]
+fn pools() {
+    assert_eq!(
+        parseCommand(
+            "pool hero.hp hero.vit * 10; add hp.max 20; config refill proportional; config refill keep"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Pool(
+                "hero.hp".to_string(),
+                binary(
+                    BiOperation::Multiply,
+                    Expression::Variable("hero.vit".to_string()),
+                    value(10)
+                )
+            ),
+            Command::Add("hp.max".to_string(), value(20)),
+            Command::Config(Setting::Refill(Refill::Proportional)),
+            Command::Config(Setting::Refill(Refill::Keep)),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("pool hp".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("pool 2hp 10".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2hp".to_string()))
+    );
+    assert_eq!(
+        parseCommand("config refill full".to_string()),
+        Err(ParsingErr::UnrecognizedSetting("refill full".to_string()))
+    );
+}
+
+#[test]
+fn effects() {
+    assert_eq!(
+        parseCommand(
+            "effect \"Blessing of Dawn\" hero.str 5 for 3; effect curse hp.max -10 for 1 chapter; tick; advance 2; chapter; effects; effects hero"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Effect(
+                "Blessing of Dawn".to_string(),
+                "hero.str".to_string(),
+                value(5),
+                value(3),
+                TimeUnit::Ticks
+            ),
+            Command::Effect(
+                "curse".to_string(),
+                "hp.max".to_string(),
+                value(-10),
+                value(1),
+                TimeUnit::Chapters
+            ),
+            Command::Tick(value(1)),
+            Command::Tick(value(2)),
+            Command::Chapter,
+            Command::Effects(None),
+            Command::Effects(Some("hero".to_string())),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("effect curse hp -10 2".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("effect curse 2hp -10 for 2".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2hp".to_string()))
+    );
+    assert_eq!(
+        parseCommand("chapter 2".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn curves() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseCommand(
+            "curve xp level = 100 * level then levelup(hero); curve hero.xp hero.level = table 0, 100, 150 + 150"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Curve(
+                "xp".to_string(),
+                Curve {
+                    level: "level".to_string(),
+                    thresholds: Thresholds::Formula(binary(
+                        BiOperation::Multiply,
+                        value(100),
+                        variable("level")
+                    )),
+                    hook: Some(("levelup".to_string(), vec![variable("hero")])),
+                }
+            ),
+            Command::Curve(
+                "hero.xp".to_string(),
+                Curve {
+                    level: "hero.level".to_string(),
+                    thresholds: Thresholds::Table(vec![
+                        value(0),
+                        value(100),
+                        binary(BiOperation::Add, value(150), value(150))
+                    ]),
+                    hook: None,
+                }
+            ),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("curve xp level 100 * level".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("100".to_string()))
+    );
+    assert_eq!(
+        parseCommand("curve xp 2level = 100".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2level".to_string()))
+    );
+    assert_eq!(
+        parseCommand("curve xp level =".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn modifiers() {
+    assert_eq!(
+        parseCommand(
+            "modifier hero.str \"Ring of Might\" 8; modifier str haste 5 * 2 percent; revoke \"Ring of Might\"; explain hero.str"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Modifier(
+                "hero.str".to_string(),
+                "Ring of Might".to_string(),
+                value(8),
+                false
+            ),
+            Command::Modifier(
+                "str".to_string(),
+                "haste".to_string(),
+                binary(BiOperation::Multiply, value(5), value(2)),
+                true
+            ),
+            Command::Revoke("Ring of Might".to_string()),
+            Command::Explain("hero.str".to_string()),
+        ])
+    );
+
+    // A trailing `%` makes the modifier a percentage, rather than a flat bonus of a fraction.
+    assert_eq!(
+        parseCommand("modifier str haste 15%; modifier str rage 5 * 3%".to_string()),
+        Ok(vec![
+            Command::Modifier("str".to_string(), "haste".to_string(), value(15), true),
+            Command::Modifier(
+                "str".to_string(),
+                "rage".to_string(),
+                binary(BiOperation::Multiply, value(5), value(3)),
+                true
+            ),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("modifier str haste".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("revoke".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("explain 2str".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2str".to_string()))
+    );
+}
+
+#[test]
+fn equipment() {
+    assert_eq!(
+        parseCommand(
+            "item \"Iron Sword\" weapon { str: 5, agi: 1 + 1 }; equip hero \"Iron Sword\"; unequip hero weapon"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Item(
+                "Iron Sword".to_string(),
+                "weapon".to_string(),
+                vec![
+                    ("str".to_string(), value(5)),
+                    (
+                        "agi".to_string(),
+                        binary(BiOperation::Add, value(1), value(1))
+                    ),
+                ]
+            ),
+            Command::Equip("hero".to_string(), "Iron Sword".to_string()),
+            Command::Unequip("hero".to_string(), "weapon".to_string()),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("item sword hand.left { str: 5 }".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("hand.left".to_string()))
+    );
+    assert_eq!(
+        parseCommand("equip hero".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("unequip hero \"weapon\"".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("\"weapon\"".to_string()))
+    );
+}
+
+#[test]
+fn allocation() {
+    assert_eq!(
+        parseCommand(
+            "allocate 3 str; allocate 1 + 1 hero.vit; allocate 2 hero.agi from bonus".to_string()
+        ),
+        Ok(vec![
+            Command::Allocate("str".to_string(), "points".to_string(), value(3)),
+            Command::Allocate(
+                "hero.vit".to_string(),
+                "hero.points".to_string(),
+                binary(BiOperation::Add, value(1), value(1))
+            ),
+            Command::Allocate("hero.agi".to_string(), "bonus".to_string(), value(2)),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("allocate str".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("allocate 3 str from 2bonus".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2bonus".to_string()))
+    );
+
+    assert_eq!(
+        parseCommand("points hero.points 2 * 5".to_string()),
+        Ok(vec![Command::Points(
+            "hero.points".to_string(),
+            binary(BiOperation::Multiply, value(2), value(5))
+        )])
+    );
+    assert_eq!(
+        parseCommand("points 5".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+}
+
+#[test]
+fn skills() {
+    let variable = |name: &str| Expression::Variable(name.to_string());
+
+    assert_eq!(
+        parseCommand(
+            "skill Spark 1; skill \"Fire Ball\" 5 requires Spark, max(int, wis) >= 20; learn hero Spark; rankup hero \"Fire Ball\"; skills hero"
+                .to_string()
+        ),
+        Ok(vec![
+            Command::Skill("Spark".to_string(), value(1), vec![]),
+            Command::Skill(
+                "Fire Ball".to_string(),
+                value(5),
+                vec![
+                    variable("Spark"),
+                    binary(
+                        BiOperation::GreaterOrEqual,
+                        Expression::Call(
+                            "max".to_string(),
+                            vec![variable("int"), variable("wis")]
+                        ),
+                        value(20)
+                    ),
+                ]
+            ),
+            Command::Learn("hero".to_string(), "Spark".to_string()),
+            Command::Rankup("hero".to_string(), "Fire Ball".to_string()),
+            Command::Skills("hero".to_string()),
+        ])
+    );
+
+    assert_eq!(
+        parseCommand("skill Spark".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("learn hero".to_string()),
+        Err(ParsingErr::InvalidNumberOfArguments)
+    );
+    assert_eq!(
+        parseCommand("skills 2hero".to_string()),
+        Err(ParsingErr::UnrecognizedExpression("2hero".to_string()))
+    );
 }
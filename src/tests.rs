@@ -5,7 +5,7 @@ use crate::parser::parseFile;
 
 #[test]
 fn integrated() {
-    let whole_tests: Vec<(String, String)> = vec![
+    let whole_tests: Vec<(String, String)> = [
         ("test.txt", "expected_output.txt"),
         ("test.md", "expected_output.md"),
     ]
@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+/// Every limb holds 9 decimal digits, which makes printing and parsing the number trivial,
+/// while the product of two limbs still comfortably fits into a `u64`.
+const BASE: u64 = 1_000_000_000;
+const DIGITS_PER_LIMB: usize = 9;
+
+/// Numbers with more limbs than this are refused, as a typo like `10 ^ 10 ^ 10` would otherwise
+/// happily eat all of the memory. This is still over 36 000 decimal digits.
+pub const MAX_LIMBS: usize = 4096;
+
+/// An integer of unbounded size, used when the author turns on the big number mode.
+///
+/// It's stored as a sign and a magnitude, with the limbs of the magnitude going from the least significant one.
+/// There are never any zero limbs at the end, so zero is simply an empty vector (and it's never negative).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn fromParts(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    /// Parses a string of decimal digits (without a sign). Returns `None` if there is anything else in it.
+    pub fn parse(digits: &str) -> Option<BigInt> {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        // The limbs are taken from the end of the string, as the last digits are the least significant ones.
+        let limbs = digits
+            .as_bytes()
+            .rchunks(DIGITS_PER_LIMB)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0u32, |limb, digit| limb * 10 + (digit - b'0') as u32)
+            })
+            .collect();
+
+        Some(BigInt::fromParts(false, limbs))
+    }
+
+    pub fn isZero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn isNegative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn limbCount(&self) -> usize {
+        self.limbs.len()
+    }
+
+    pub fn toI64(&self) -> Option<i64> {
+        // The magnitude is accumulated as a negative number, since that side of `i64` is one bigger.
+        let mut result: i64 = 0;
+        for limb in self.limbs.iter().rev() {
+            result = result.checked_mul(BASE as i64)?.checked_sub(*limb as i64)?;
+        }
+
+        if self.negative {
+            Some(result)
+        } else {
+            result.checked_neg()
+        }
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::fromParts(!self.negative, self.limbs.clone())
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::fromParts(false, self.limbs.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::fromParts(self.negative, addMagnitudes(&self.limbs, &other.limbs));
+        }
+
+        // With different signs, the smaller magnitude is subtracted from the bigger one, which also decides the sign.
+        match compareMagnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                BigInt::fromParts(other.negative, subMagnitudes(&other.limbs, &self.limbs))
+            }
+            _ => BigInt::fromParts(self.negative, subMagnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::fromParts(
+            self.negative != other.negative,
+            mulMagnitudes(&self.limbs, &other.limbs),
+        )
+    }
+
+    /// Divides the numbers, with the quotient rounded towards zero. The remainder has the sign of the dividend.
+    ///
+    /// Returns `None` when dividing by zero.
+    pub fn divRem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.isZero() {
+            return None;
+        }
+
+        let (quotient, remainder) = divRemMagnitudes(&self.limbs, &other.limbs);
+
+        Some((
+            BigInt::fromParts(self.negative != other.negative, quotient),
+            BigInt::fromParts(self.negative, remainder),
+        ))
+    }
+
+    /// Raises the number to the given power by repeated squaring.
+    ///
+    /// Returns `None` as soon as the result would be bigger than `MAX_LIMBS`.
+    pub fn pow(&self, mut exponent: u32) -> Option<BigInt> {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base);
+                if result.limbCount() > MAX_LIMBS {
+                    return None;
+                }
+            }
+
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base);
+                if base.limbCount() > MAX_LIMBS {
+                    return None;
+                }
+            }
+        }
+
+        Some(result)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(val: i64) -> Self {
+        BigInt::fromWide(val as i128)
+    }
+}
+
+impl BigInt {
+    /// Not a `From` implementation, as that would make every `BigInt::from(1)` ambiguous.
+    pub fn fromWide(val: i128) -> BigInt {
+        let mut magnitude = val.unsigned_abs();
+        let mut limbs = Vec::new();
+
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE as u128) as u32);
+            magnitude /= BASE as u128;
+        }
+
+        BigInt::fromParts(val < 0, limbs)
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compareMagnitudes(&self.limbs, &other.limbs),
+            (true, true) => compareMagnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.limbs.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                if self.negative {
+                    write!(f, "-")?;
+                }
+
+                // Only the most significant limb is written as is, the rest have to keep their leading zeroes.
+                write!(f, "{most_significant}")?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{limb:09}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn compareMagnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn addMagnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    result
+}
+
+/// Subtracts the magnitudes. The first one has to be at least as big as the second one.
+fn subMagnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+
+    for (i, limb) in a.iter().enumerate() {
+        let mut difference = *limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += BASE as i64;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+
+    result
+}
+
+fn mulMagnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let mut result = vec![0u64; a.len() + b.len()];
+
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let current = result[i + j] + (*x as u64) * (*y as u64) + carry;
+            result[i + j] = current % BASE;
+            carry = current / BASE;
+        }
+        result[i + b.len()] += carry;
+    }
+
+    result.into_iter().map(|limb| limb as u32).collect()
+}
+
+/// Schoolbook long division. Each limb of the quotient is found with a binary search, which is slow-ish,
+/// but simple, and the numbers in a book never get big enough for it to matter.
+fn divRemMagnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let divisor = BigInt::fromParts(false, b.to_vec());
+
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = BigInt::default();
+
+    for (i, limb) in a.iter().enumerate().rev() {
+        // Shift the remainder by one limb and bring down the next one.
+        let mut shifted = vec![*limb];
+        shifted.extend_from_slice(&remainder.limbs);
+        remainder = BigInt::fromParts(false, shifted);
+
+        let (mut low, mut high) = (0u64, BASE - 1);
+        while low < high {
+            let middle = (low + high).div_ceil(2);
+            if divisor.mul(&BigInt::from(middle as i64)) <= remainder {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+
+        quotient[i] = low as u32;
+        remainder = remainder.sub(&divisor.mul(&BigInt::from(low as i64)));
+    }
+
+    (quotient, remainder.limbs)
+}
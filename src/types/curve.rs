@@ -0,0 +1,47 @@
+use std::fmt::Display;
+
+use super::Expression;
+
+/// How much experience each level takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Thresholds {
+    /// The total experience needed to reach a level, with the name of the level standing for that level,
+    /// like `100 * level ^ 2`.
+    Formula(Expression),
+    /// The total experience needed for every level, starting with the first one. There are no levels past
+    /// the end of the table.
+    Table(Vec<Expression>),
+}
+
+/// An experience curve, which raises the level whenever the experience reaches the threshold of the next one.
+///
+/// The hook is a macro, which is called after every level gained, so it can raise the stats or write something
+/// like "[Level Up!]".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Curve {
+    pub level: String,
+    pub thresholds: Thresholds,
+    pub hook: Option<(String, Vec<Expression>)>,
+}
+
+/// Written like `level from 100 * level ^ 2, then levelup(hero)`.
+impl Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.thresholds {
+            Thresholds::Formula(formula) => write!(f, "{} from {formula}", self.level)?,
+            Thresholds::Table(table) => {
+                let table: Vec<String> = table.iter().map(|entry| entry.to_string()).collect();
+                write!(f, "{} from the table {}", self.level, table.join(", "))?
+            }
+        }
+
+        match &self.hook {
+            Some((name, arguments)) => write!(
+                f,
+                ", then {}",
+                Expression::Call(name.to_owned(), arguments.to_vec())
+            ),
+            None => Ok(()),
+        }
+    }
+}
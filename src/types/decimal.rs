@@ -0,0 +1,242 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use super::bigint::BigInt;
+use super::engine::Rounding;
+
+/// The most decimal places a number can have. Anything more would leave too little room for the whole part.
+pub const MAX_PRECISION: u32 = 18;
+
+/// A fixed-point decimal number, for percentages and multipliers like `15%` or `1.25`.
+///
+/// The value is `units / 10 ^ scale`, so all of the arithmetic is done on integers and there is no floating point
+/// drift. Trailing zeroes are always removed from the units, so every value has exactly one representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mut units: i128, mut scale: u32) -> Decimal {
+        while scale > 0 && units % 10 == 0 {
+            units /= 10;
+            scale -= 1;
+        }
+
+        Decimal { units, scale }
+    }
+
+    /// Parses a number written as `12.345` (the decimal point is optional).
+    /// It can't have more than `MAX_PRECISION` decimal places.
+    pub fn parse(input: &str) -> Option<Decimal> {
+        let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+
+        if whole.is_empty() || !(whole.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let scale = u32::try_from(fraction.len())
+            .ok()
+            .filter(|scale| *scale <= MAX_PRECISION)?;
+        let units = format!("{whole}{fraction}").parse::<i128>().ok()?;
+
+        Some(Decimal::new(units, scale))
+    }
+
+    pub fn fromBig(big: &BigInt) -> Option<Decimal> {
+        // Big numbers are rare enough that going through the text representation is perfectly fine.
+        big.to_string()
+            .parse::<i128>()
+            .ok()
+            .map(|units| Decimal::new(units, 0))
+    }
+
+    pub fn isZero(&self) -> bool {
+        self.units == 0
+    }
+
+    pub fn isNegative(&self) -> bool {
+        self.units < 0
+    }
+
+    /// Returns the value as an integer, if it doesn't have a fractional part.
+    pub fn toWhole(&self) -> Option<i64> {
+        if self.scale == 0 {
+            i64::try_from(self.units).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Turns `15` into `0.15`.
+    pub fn percent(&self) -> Decimal {
+        Decimal::new(self.units, self.scale + 2)
+    }
+
+    pub fn neg(&self) -> Option<Decimal> {
+        Some(Decimal::new(self.units.checked_neg()?, self.scale))
+    }
+
+    /// Returns the units of both numbers at the same scale, which is the bigger one of the two.
+    fn aligned(&self, other: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+
+        Some((
+            self.units
+                .checked_mul(10i128.checked_pow(scale - self.scale)?)?,
+            other
+                .units
+                .checked_mul(10i128.checked_pow(scale - other.scale)?)?,
+            scale,
+        ))
+    }
+
+    pub fn add(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::new(a.checked_add(b)?, scale))
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::new(a.checked_sub(b)?, scale))
+    }
+
+    /// Multiplies the numbers, and if the result has more than `precision` decimal places, rounds it.
+    pub fn mul(&self, other: &Decimal, precision: u32, rounding: Rounding) -> Option<Decimal> {
+        Decimal {
+            units: self.units.checked_mul(other.units)?,
+            scale: self.scale + other.scale,
+        }
+        .rescaled(precision, rounding)
+    }
+
+    /// Divides the numbers, with the result rounded to `precision` decimal places. Returns `None` when
+    /// dividing by zero.
+    pub fn div(&self, other: &Decimal, precision: u32, rounding: Rounding) -> Option<Decimal> {
+        // a / b = (A / 10^sa) / (B / 10^sb), so to get the units of the result at the given precision,
+        // A * 10^(precision + sb) has to be divided by B * 10^sa.
+        let numerator = self
+            .units
+            .checked_mul(10i128.checked_pow(precision + other.scale)?)?;
+        let denominator = other.units.checked_mul(10i128.checked_pow(self.scale)?)?;
+
+        Some(Decimal::new(
+            divideRounded(numerator, denominator, rounding)?,
+            precision,
+        ))
+    }
+
+    /// Raises the number to a whole power, rounding to `precision` decimal places after every multiplication.
+    pub fn pow(&self, mut exponent: u32, precision: u32, rounding: Rounding) -> Option<Decimal> {
+        let mut result = Decimal::new(1, 0);
+        let mut base = *self;
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base, precision, rounding)?;
+            }
+
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base, precision, rounding)?;
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Rounds the number to at most `scale` decimal places.
+    fn rescaled(&self, scale: u32, rounding: Rounding) -> Option<Decimal> {
+        if self.scale <= scale {
+            return Some(Decimal::new(self.units, self.scale));
+        }
+
+        let divisor = 10i128.checked_pow(self.scale - scale)?;
+        Some(Decimal::new(
+            divideRounded(self.units, divisor, rounding)?,
+            scale,
+        ))
+    }
+
+    /// Writes the number with exactly the given amount of decimal places.
+    pub fn format(&self, decimals: u32, rounding: Rounding) -> Option<String> {
+        let rounded = self.rescaled(decimals, rounding)?;
+
+        // Pad the units back up to the requested scale, so that `1.5` written with two decimals is `1.50`.
+        let units = rounded
+            .units
+            .checked_mul(10i128.checked_pow(decimals - rounded.scale)?)?;
+
+        Some(writeUnits(units, decimals))
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(val: i64) -> Self {
+        Decimal::new(val as i128, 0)
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.aligned(other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // If the units can't be aligned without overflowing, they're compared as big integers instead.
+            None => {
+                let scale = self.scale.max(other.scale);
+                let aligned = |decimal: &Decimal| {
+                    let shift = BigInt::from(10)
+                        .pow(scale - decimal.scale)
+                        .unwrap_or_default();
+                    BigInt::fromWide(decimal.units).mul(&shift)
+                };
+
+                aligned(self).cmp(&aligned(other))
+            }
+        }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", writeUnits(self.units, self.scale))
+    }
+}
+
+/// Writes `units / 10^scale` with exactly `scale` decimal places.
+fn writeUnits(units: i128, scale: u32) -> String {
+    let sign = if units < 0 { "-" } else { "" };
+    let digits = format!(
+        "{:0>width$}",
+        units.unsigned_abs(),
+        width = scale as usize + 1
+    );
+    let (whole, fraction) = digits.split_at(digits.len() - scale as usize);
+
+    if fraction.is_empty() {
+        format!("{sign}{whole}")
+    } else {
+        format!("{sign}{whole}.{fraction}")
+    }
+}
+
+/// Divides the numbers, with the result rounded the given way. Returns `None` when dividing by zero or overflowing.
+pub fn divideRounded(lhand: i128, rhand: i128, rounding: Rounding) -> Option<i128> {
+    let quotient = lhand.checked_div(rhand)?;
+    let remainder = lhand % rhand;
+    let adjustment = rounding.adjustment(
+        remainder != 0,
+        (lhand < 0) != (rhand < 0),
+        remainder.unsigned_abs() >= rhand.unsigned_abs() - remainder.unsigned_abs(),
+    );
+
+    quotient.checked_add(i128::from(adjustment))
+}
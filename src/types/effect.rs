@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use super::number::Number;
+
+/// What the duration of an effect is counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Counted down by the `tick` command, so the author decides what a tick is (a day, a turn, an hour...).
+    Ticks,
+    /// Counted down at the end of every chapter.
+    Chapters,
+}
+
+/// A temporary change of a variable, like "Blessing of Dawn: +5 str for 3 days".
+///
+/// On a number, the effect is a modifier named after it, which is removed once the effect runs out, so anything
+/// that happened to the variable in the meantime is kept, and `explain` shows where the bonus came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Effect {
+    pub name: String,
+    pub target: String,
+    pub amount: Number,
+    pub remaining: u32,
+    pub unit: TimeUnit,
+    /// A pool can't have modifiers, as it's kept between zero and its maximum, so the amount is added to it
+    /// instead. This is how much it actually changed by, which is taken back once the effect runs out.
+    pub change: Option<Number>,
+}
+
+impl Effect {
+    /// Whether the effect is on the variable, or on one of the fields of the entity.
+    pub fn affects(&self, name: &str) -> bool {
+        self.target == name
+            || self
+                .target
+                .strip_prefix(name)
+                .is_some_and(|field| field.starts_with('.'))
+    }
+}
+
+/// Written like `Blessing of Dawn (hero.str +5, 3 ticks left)`.
+impl Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.amount.isNegative() { "" } else { "+" };
+        let unit = match (self.unit, self.remaining) {
+            (TimeUnit::Ticks, 1) => "tick",
+            (TimeUnit::Ticks, _) => "ticks",
+            (TimeUnit::Chapters, 1) => "chapter",
+            (TimeUnit::Chapters, _) => "chapters",
+        };
+
+        write!(
+            f,
+            "{} ({} {sign}{}, {} {unit} left)",
+            self.name, self.target, self.amount, self.remaining
+        )
+    }
+}
@@ -1,10 +1,150 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 
+use super::curve::Curve;
+use super::effect::{Effect, TimeUnit};
 use super::error::RunErr;
+use super::modifier::{Modifier, Origin};
+use super::number::Number;
+use super::random::Rng;
+use super::value::Value;
+use super::{Content, Expression, Setting};
+
+/// How the result of a division is rounded when it isn't a whole number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards negative infinity, so `-7 / 2` is `-4`.
+    #[default]
+    Floor,
+    /// Towards zero, so `-7 / 2` is `-3`.
+    Truncate,
+    /// To the nearest whole number, with halves rounded away from zero, so `-7 / 2` is `-4` and `5 / 3` is `2`.
+    Round,
+}
+
+impl Rounding {
+    /// How much the truncated quotient of a division has to be moved to be rounded this way: one step away from
+    /// zero or not at all. `negative` is whether the exact result is negative, so that away from zero means going
+    /// down, and `half` is whether the remainder is at least half of the divisor. Without a remainder, the quotient
+    /// is already exact.
+    pub fn adjustment(self, remainder: bool, negative: bool, half: bool) -> i8 {
+        let away = match self {
+            Rounding::Truncate => false,
+            Rounding::Floor => negative,
+            Rounding::Round => half,
+        };
+
+        match (remainder && away, negative) {
+            (false, _) => 0,
+            (true, true) => -1,
+            (true, false) => 1,
+        }
+    }
+}
+
+/// Which numbers the variables and expressions can hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberMode {
+    /// 64-bit numbers, going a bit over 9 quintillion either way. Anything bigger is an overflow.
+    #[default]
+    Fixed,
+    /// Numbers of any size, for when even that isn't enough.
+    Big,
+}
+
+/// What happens to the current value of a pool when its maximum changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Refill {
+    /// It stays the same, unless it's over the new maximum.
+    #[default]
+    Keep,
+    /// The pool stays as full as it was, so half of the old maximum becomes half of the new one.
+    Proportional,
+}
+
+/// The settings the author can change while the file is being interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub rounding: Rounding,
+    pub numbers: NumberMode,
+    /// How many decimal places are kept when multiplying or dividing decimal numbers.
+    pub precision: u32,
+    /// How many times a single loop can run, so that a loop which never ends fails instead of hanging.
+    pub iterations: u32,
+    /// How many macro calls can be running at once, so that a macro which keeps calling itself fails
+    /// instead of overflowing the stack.
+    pub depth: u32,
+    pub refill: Refill,
+}
+
+/// The highest the limit of macro calls can be set to. Every call takes up a bit of the stack, so it can't
+/// go on forever.
+pub const MAX_DEPTH: u32 = 128;
+
+/// How many derived variables can be read inside of each other, like `a` derived from `b` derived from `c`.
+/// Just like macro calls, every one of them takes up a bit of the stack.
+pub const MAX_DERIVED_DEPTH: u32 = 128;
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            rounding: Rounding::default(),
+            numbers: NumberMode::default(),
+            precision: 4,
+            iterations: 10000,
+            depth: 64,
+            refill: Refill::default(),
+        }
+    }
+}
+
+/// The bonuses an item gives, as the names of the stats and the amounts to add to them.
+pub type Bonuses = [(String, Expression)];
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Storage {
-    variables: HashMap<String, i32>,
+    settings: Settings,
+    variables: HashMap<String, Value>,
+    /// Derived variables don't hold a value, but the expression used to compute it.
+    derived: HashMap<String, Expression>,
+    /// The derived variables which read each variable directly, so that they can be found when it changes.
+    dependents: HashMap<String, HashSet<String>>,
+    /// The values of the derived variables computed so far, with how many derived variables deep their chain
+    /// goes. A value stays here until one of the variables it depends on changes, so reading it again (or reading
+    /// it from several other derived variables) doesn't compute it again. It's filled in while expressions are
+    /// evaluated, which only get to read the storage.
+    cache: RefCell<HashMap<String, (Value, u32)>>,
+    /// How many derived variables are being computed inside of each other right now.
+    deriving: Cell<u32>,
+    /// How deep the chains of the derived variables read by the one being computed go.
+    height: Cell<u32>,
+    /// Entities group variables under one name, so `hero.str` is the field `str` of the entity `hero`.
+    /// The fields themselves are stored as any other variable, under their full name, and the entity only
+    /// remembers their names, in the order they were created in.
+    entities: HashMap<String, Vec<String>>,
+    /// Classes are templates for entities, with the formula of every field.
+    classes: HashMap<String, Vec<(String, Expression)>>,
+    /// The parameters and contents of every macro.
+    macros: HashMap<String, (Vec<String>, Vec<Content>)>,
+    /// How many macro calls are running right now.
+    depth: u32,
+    rng: Rng,
+    /// The effects which haven't run out yet, in the order they started in.
+    effects: Vec<Effect>,
+    /// The experience curves, by the name of the variable holding the experience.
+    curves: HashMap<String, Curve>,
+    /// The modifiers of every stat, in the order they were added in.
+    modifiers: HashMap<String, Vec<Modifier>>,
+    /// The slot and the bonuses of every item which can be equipped.
+    items: HashMap<String, (String, Vec<(String, Expression)>)>,
+    /// What's in the slots of the entities, by the full name of the slot, like `hero.weapon`.
+    equipped: HashMap<String, String>,
+    /// The maximum rank and the prerequisites of every skill.
+    skills: HashMap<String, (u32, Vec<Expression>)>,
+    /// The skills every entity has learned, with their ranks, in the order they were learned in.
+    ranks: HashMap<String, Vec<(String, u32)>>,
+    /// The variables holding unspent points. They're numbers like any other, except that they can't go below zero.
+    points: HashSet<String>,
 }
 
 #[allow(unused)]
@@ -15,36 +155,578 @@ impl Storage {
 
     pub fn clear(&mut self) {
         self.variables.clear();
+        self.derived.clear();
+        self.dependents.clear();
+        self.cache.get_mut().clear();
+        self.entities.clear();
+        self.classes.clear();
+        self.macros.clear();
+        self.depth = 0;
+        self.effects.clear();
+        self.curves.clear();
+        self.modifiers.clear();
+        self.items.clear();
+        self.equipped.clear();
+        self.skills.clear();
+        self.ranks.clear();
+        self.points.clear();
     }
 
-    pub fn createVariable(&mut self, variable: String, val: i32) -> Result<(), RunErr> {
-        // This is a kinda weird bit of code (both because of the default rust formatting and because it's written that way)
-        // but what I wanted to do was to simply return an error if the `insert` function returned Some().
-        // This is because the `let` command is supposed to initialize a variable, and if it returns Some(), it means that there was a variable
-        // with that name.
-        self.variables
-            .insert(variable, val)
-            .map_or(Ok(()), |_| Err((RunErr::TriedToInitializeExistingVariable)))
+    /// Whether the name is taken by anything, be it a variable, a derived variable or an entity.
+    fn exists(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+            || self.derived.contains_key(name)
+            || self.entities.contains_key(name)
+    }
+
+    /// If the variable is a field (its name has a dot in it), checks that its entity exists and adds it to
+    /// the fields of the entity.
+    fn registerField(&mut self, variable: &str) -> Result<(), RunErr> {
+        if let Some((entity, field)) = variable.rsplit_once('.') {
+            self.entities
+                .get_mut(entity)
+                .ok_or_else(|| RunErr::NonexistentEntity(entity.to_string()))?
+                .push(field.to_string());
+        }
+        Ok(())
+    }
+
+    fn unregisterField(&mut self, variable: &str) {
+        if let Some((entity, field)) = variable.rsplit_once('.') {
+            if let Some(fields) = self.entities.get_mut(entity) {
+                fields.retain(|existing| existing != field);
+            }
+        }
+    }
+
+    pub fn createEntity(&mut self, entity: String) -> Result<(), RunErr> {
+        if self.exists(&entity) {
+            return Err(RunErr::TriedToInitializeExistingVariable);
+        }
+
+        // An entity can be a part of another one, like `hero.pet`, but it isn't one of its fields.
+        if let Some((owner, _)) = entity.rsplit_once('.') {
+            if !self.entities.contains_key(owner) {
+                return Err(RunErr::NonexistentEntity(owner.to_string()));
+            }
+        }
+
+        self.entities.insert(entity, Vec::new());
+        Ok(())
+    }
+
+    /// Removes the entity together with all of its fields, and any entities which are a part of it.
+    pub fn deleteEntity(&mut self, entity: &str) -> Result<(), RunErr> {
+        if self.entities.remove(entity).is_none() {
+            return Err(RunErr::NonexistentEntity(entity.to_string()));
+        }
+
+        let prefix = format!("{entity}.");
+        let removed: Vec<String> = (self.variables.keys())
+            .chain(self.derived.keys())
+            .filter(|name| name.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for name in &removed {
+            self.invalidate(name);
+        }
+        self.variables.retain(|name, _| !name.starts_with(&prefix));
+        self.derived.retain(|name, _| !name.starts_with(&prefix));
+        for dependents in self.dependents.values_mut() {
+            dependents.retain(|name| !name.starts_with(&prefix));
+        }
+        self.entities.retain(|name, _| !name.starts_with(&prefix));
+        self.effects.retain(|effect| !effect.affects(entity));
+        self.curves.retain(|name, _| !name.starts_with(&prefix));
+        self.modifiers.retain(|name, _| !name.starts_with(&prefix));
+        self.equipped.retain(|slot, _| !slot.starts_with(&prefix));
+        self.ranks
+            .retain(|owner, _| owner != entity && !owner.starts_with(&prefix));
+        self.points.retain(|name| !name.starts_with(&prefix));
+        self.unregisterField(entity);
+
+        Ok(())
+    }
+
+    /// Returns the names of the fields of the entity (without the name of the entity), in the order they were
+    /// created in.
+    pub fn getFields(&self, entity: &str) -> Result<&[String], RunErr> {
+        self.entities
+            .get(entity)
+            .map(|fields| fields.as_slice())
+            .ok_or_else(|| RunErr::NonexistentEntity(entity.to_string()))
     }
 
-    pub fn modifyVariable<F: FnOnce(i32) -> i32>(
+    pub fn defineClass(
+        &mut self,
+        class: String,
+        fields: Vec<(String, Expression)>,
+    ) -> Result<(), RunErr> {
+        if self.classes.contains_key(&class) {
+            return Err(RunErr::TriedToRedefineClass(class));
+        }
+
+        self.classes.insert(class, fields);
+        Ok(())
+    }
+
+    /// Returns the fields of the class with their formulas, in the order they were written in.
+    pub fn getClass(&self, class: &str) -> Result<&[(String, Expression)], RunErr> {
+        self.classes
+            .get(class)
+            .map(|fields| fields.as_slice())
+            .ok_or_else(|| RunErr::NonexistentClass(class.to_string()))
+    }
+
+    /// Unlike classes, macros can be defined again, in which case the new definition replaces the old one.
+    /// That way a macro defined inside of a loop doesn't fail on the second iteration.
+    pub fn defineMacro(&mut self, name: String, parameters: Vec<String>, contents: Vec<Content>) {
+        self.macros.insert(name, (parameters, contents));
+    }
+
+    pub fn getMacro(&self, name: &str) -> Result<(&[String], &[Content]), RunErr> {
+        self.macros
+            .get(name)
+            .map(|(parameters, contents)| (parameters.as_slice(), contents.as_slice()))
+            .ok_or_else(|| RunErr::UnknownMacro(name.to_string()))
+    }
+
+    /// Marks the start of a macro call, failing if there are already as many running as the settings allow.
+    pub fn enterMacro(&mut self) -> Result<(), RunErr> {
+        if self.depth >= self.settings.depth {
+            return Err(RunErr::RecursionLimit(self.settings.depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leaveMacro(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn createVariable(&mut self, variable: String, val: Value) -> Result<(), RunErr> {
+        // The `let` command is supposed to initialize a variable, so a name which is taken is an error, and the
+        // variable which has it is left alone.
+        if self.exists(&variable) {
+            return Err(RunErr::TriedToInitializeExistingVariable);
+        }
+
+        self.registerField(&variable)?;
+        self.invalidate(&variable);
+        self.variables.insert(variable, val);
+        Ok(())
+    }
+
+    /// Replaces the value of the variable with the result of the function. The function can fail (for example
+    /// when the new value would overflow), in which case the variable is left untouched.
+    pub fn modifyVariable<F: FnOnce(Value) -> Result<Value, RunErr>>(
         &mut self,
         key: String,
         func: F,
     ) -> Result<(), RunErr> {
         match self.variables.get(&key) {
             Some(contents) => {
-                self.variables.insert(key, func(*contents));
+                let new_value = func(contents.clone())?;
+
+                // Unspent points can't be spent if they aren't there.
+                if let (true, Value::Number(available), Value::Number(left)) =
+                    (self.points.contains(&key), contents, &new_value)
+                {
+                    if left.isNegative() {
+                        let wanted = available.sub(left).map(|wanted| wanted.to_string());
+                        return Err(RunErr::NotEnoughPoints(
+                            key,
+                            wanted.unwrap_or_default(),
+                            available.to_string(),
+                        ));
+                    }
+                }
+
+                self.invalidate(&key);
+                self.variables.insert(key, new_value);
                 Ok(())
             }
+            None if self.derived.contains_key(&key) => {
+                Err(RunErr::TriedToModifyDerivedVariable(key))
+            }
             None => Err(RunErr::TriedToModifyNonexistentVariable),
         }
     }
 
-    pub fn getValue(&self, key: String) -> Result<i32, RunErr> {
+    /// Creates unspent points, which can't be given a value below zero.
+    pub fn createPoints(&mut self, variable: String, points: Number) -> Result<(), RunErr> {
+        self.createVariable(variable.clone(), Value::Number(points))?;
+        self.points.insert(variable);
+        Ok(())
+    }
+
+    /// Whether the variable holds unspent points.
+    pub fn isPoints(&self, variable: &str) -> bool {
+        self.points.contains(variable)
+    }
+
+    /// Removes the variable, for the ones which only live as long as a block, like the index of a loop.
+    pub fn removeVariable(&mut self, key: &str) {
+        if self.variables.remove(key).is_some() {
+            self.unregisterField(key);
+            self.invalidate(key);
+        }
+    }
+
+    pub fn getValue(&self, key: String) -> Result<Value, RunErr> {
         self.variables
             .get(&key)
-            .copied()
+            .cloned()
             .ok_or(RunErr::TriedToGetNonexistentVariable)
     }
+
+    /// Creates a variable whose value is always computed from the given expression.
+    ///
+    /// Before it's created, the dependencies of the expression are followed through the other derived variables,
+    /// and if any of them lead back to this variable, it's rejected, as its value could never be computed.
+    pub fn createDerived(
+        &mut self,
+        variable: String,
+        expression: Expression,
+    ) -> Result<(), RunErr> {
+        if self.exists(&variable) {
+            return Err(RunErr::TriedToInitializeExistingVariable);
+        }
+
+        let mut to_visit: Vec<String> = expression.variables();
+        let mut visited: HashSet<String> = HashSet::new();
+
+        while let Some(dependency) = to_visit.pop() {
+            if dependency == variable {
+                return Err(RunErr::CyclicDependency(variable));
+            }
+
+            if !visited.contains(&dependency) {
+                if let Some(expr) = self.derived.get(&dependency) {
+                    to_visit.append(&mut expr.variables());
+                }
+                visited.insert(dependency);
+            }
+        }
+
+        self.registerField(&variable)?;
+
+        // A part of a pool, like `hp.max`, changes together with the pool, so it depends on the pool as well.
+        for dependency in expression.variables() {
+            if let Some((pool, _)) = dependency.rsplit_once('.') {
+                self.dependents
+                    .entry(pool.to_string())
+                    .or_default()
+                    .insert(variable.clone());
+            }
+            self.dependents
+                .entry(dependency)
+                .or_default()
+                .insert(variable.clone());
+        }
+
+        self.invalidate(&variable);
+        self.derived.insert(variable, expression);
+        Ok(())
+    }
+
+    /// Returns the expression of a derived variable, or `None` if the variable isn't a derived one.
+    pub fn getDerived(&self, key: &str) -> Option<&Expression> {
+        self.derived.get(key)
+    }
+
+    /// Reads a derived variable, computing it from its expression with the given function unless it was already
+    /// computed since the last change to the variables it depends on. Returns `None` if the variable isn't a
+    /// derived one.
+    ///
+    /// Computing a derived variable can read others, so a chain which goes deeper than `MAX_DERIVED_DEPTH` fails
+    /// instead of overflowing the stack. The values are kept together with the length of the chain below them, so
+    /// whether a chain is too long doesn't depend on which of its variables were already computed.
+    pub fn readDerived<F: FnOnce(&Expression) -> Result<Value, RunErr>>(
+        &self,
+        key: &str,
+        compute: F,
+    ) -> Option<Result<Value, RunErr>> {
+        let expression = self.derived.get(key)?;
+        let depth = self.deriving.get();
+
+        let cached = self.cache.borrow().get(key).cloned();
+        let result = match cached {
+            Some((value, height)) if depth + height <= MAX_DERIVED_DEPTH => {
+                self.height.set(self.height.get().max(height));
+                Ok(value)
+            }
+            Some(_) => Err(RunErr::DerivedTooDeep(key.to_string())),
+            None if depth >= MAX_DERIVED_DEPTH => Err(RunErr::DerivedTooDeep(key.to_string())),
+            None => {
+                let outer = self.height.replace(0);
+                self.deriving.set(depth + 1);
+                let value = compute(expression);
+                self.deriving.set(depth);
+                let height = self.height.get() + 1;
+                self.height.set(outer.max(height));
+
+                if let Ok(value) = &value {
+                    self.cache
+                        .borrow_mut()
+                        .insert(key.to_string(), (value.clone(), height));
+                }
+                value
+            }
+        };
+
+        // The error names the variable which was read in the first place, rather than wherever the chain ran out.
+        Some(match result {
+            Err(RunErr::DerivedTooDeep(_)) if depth == 0 => {
+                Err(RunErr::DerivedTooDeep(key.to_string()))
+            }
+            other => other,
+        })
+    }
+
+    /// Forgets the computed values of every derived variable which depends on the variable, directly or through
+    /// other derived variables, so that they're computed again when they're read.
+    fn invalidate(&mut self, variable: &str) {
+        let cache = self.cache.get_mut();
+        let mut to_visit: Vec<&str> = vec![variable];
+        let mut visited: HashSet<&str> = HashSet::new();
+
+        while let Some(changed) = to_visit.pop() {
+            cache.remove(changed);
+            for dependent in self.dependents.get(changed).into_iter().flatten() {
+                if visited.insert(dependent) {
+                    to_visit.push(dependent);
+                }
+            }
+        }
+    }
+
+    pub fn addEffect(&mut self, effect: Effect) {
+        self.effects.push(effect);
+    }
+
+    /// Removes the effect with the name from the target, returning it, so that it can be undone.
+    pub fn takeEffect(&mut self, name: &str, target: &str) -> Option<Effect> {
+        let position = self
+            .effects
+            .iter()
+            .position(|effect| effect.name == name && effect.target == target)?;
+        Some(self.effects.remove(position))
+    }
+
+    /// Counts down the effects measured in the unit, returning the ones which ran out. It's up to the caller
+    /// to undo what they did.
+    pub fn advance(&mut self, unit: TimeUnit, amount: u32) -> Vec<Effect> {
+        let mut expired = Vec::new();
+        for effect in self.effects.iter_mut().filter(|effect| effect.unit == unit) {
+            effect.remaining = effect.remaining.saturating_sub(amount);
+        }
+
+        self.effects.retain(|effect| {
+            if effect.remaining == 0 {
+                expired.push(effect.clone());
+            }
+            effect.remaining > 0
+        });
+        expired
+    }
+
+    /// The active effects, either all of them, or only the ones on the variable or the fields of the entity.
+    pub fn getEffects(&self, target: Option<&str>) -> Vec<&Effect> {
+        self.effects
+            .iter()
+            .filter(|effect| target.is_none_or(|target| effect.affects(target)))
+            .collect()
+    }
+
+    /// Like macros, a curve can be defined again, replacing the old one, so a story can change how fast
+    /// the levels come halfway through.
+    pub fn defineCurve(&mut self, experience: String, curve: Curve) {
+        self.curves.insert(experience, curve);
+    }
+
+    /// Returns the curve of the experience, or `None` if gaining it doesn't level anything up.
+    pub fn getCurve(&self, experience: &str) -> Option<&Curve> {
+        self.curves.get(experience)
+    }
+
+    /// Adds the modifier to the stat. A stat only has one modifier from each source, so a modifier from the same
+    /// source (and of the same origin) replaces the old one, keeping its place.
+    pub fn addModifier(&mut self, stat: String, modifier: Modifier) {
+        self.invalidate(&stat);
+        let modifiers = self.modifiers.entry(stat).or_default();
+        match modifiers.iter_mut().find(|existing| {
+            existing.source == modifier.source && existing.origin == modifier.origin
+        }) {
+            Some(existing) => *existing = modifier,
+            None => modifiers.push(modifier),
+        }
+    }
+
+    /// Removes the modifiers from the source on every stat, since taking off a ring takes away everything it gave.
+    ///
+    /// Only the modifiers given by the `modifier` command can be revoked. The ones of an item go away when it's
+    /// unequipped, and the ones of an effect when it runs out, so that the slots and the effects always match the
+    /// stats.
+    pub fn revokeModifier(&mut self, source: &str) -> Result<(), RunErr> {
+        let owned =
+            |modifier: &Modifier| modifier.source == source && modifier.origin != Origin::Command;
+        if let Some(owner) = self
+            .modifiers
+            .values()
+            .flatten()
+            .find(|modifier| owned(modifier))
+        {
+            return Err(RunErr::ModifierNotRevocable(
+                source.to_string(),
+                match owner.origin {
+                    Origin::Item => "an item",
+                    Origin::Allocation => "the allocated points",
+                    _ => "an effect",
+                }
+                .to_string(),
+            ));
+        }
+
+        let mut changed = Vec::new();
+        for (stat, modifiers) in self.modifiers.iter_mut() {
+            let before = modifiers.len();
+            modifiers.retain(|modifier| modifier.source != source);
+            if modifiers.len() != before {
+                changed.push(stat.to_owned());
+            }
+        }
+        self.modifiers.retain(|_, modifiers| !modifiers.is_empty());
+        for stat in &changed {
+            self.invalidate(stat);
+        }
+
+        if !changed.is_empty() {
+            Ok(())
+        } else {
+            Err(RunErr::NonexistentModifier(source.to_string()))
+        }
+    }
+
+    /// Removes the modifier from the source on this one stat only, as another entity could be wearing the same item.
+    pub fn removeModifier(&mut self, stat: &str, source: &str, origin: Origin) {
+        self.invalidate(stat);
+        if let Some(modifiers) = self.modifiers.get_mut(stat) {
+            modifiers.retain(|modifier| modifier.source != source || modifier.origin != origin);
+            if modifiers.is_empty() {
+                self.modifiers.remove(stat);
+            }
+        }
+    }
+
+    /// The modifiers of the stat, which is empty if it doesn't have any.
+    pub fn getModifiers(&self, stat: &str) -> &[Modifier] {
+        self.modifiers.get(stat).map_or(&[], |modifiers| modifiers)
+    }
+
+    /// Like classes, items can't be redefined, so the bonuses taken away when an item is unequipped are always
+    /// the ones it gave.
+    pub fn defineItem(
+        &mut self,
+        item: String,
+        slot: String,
+        bonuses: Vec<(String, Expression)>,
+    ) -> Result<(), RunErr> {
+        if self.items.contains_key(&item) {
+            return Err(RunErr::TriedToRedefineItem(item));
+        }
+
+        self.items.insert(item, (slot, bonuses));
+        Ok(())
+    }
+
+    /// Returns the slot of the item and its bonuses.
+    pub fn getItem(&self, item: &str) -> Result<(&str, &Bonuses), RunErr> {
+        self.items
+            .get(item)
+            .map(|(slot, bonuses)| (slot.as_str(), bonuses.as_slice()))
+            .ok_or_else(|| RunErr::NonexistentItem(item.to_string()))
+    }
+
+    /// Puts the item into the slot, which has to be empty.
+    pub fn equip(&mut self, slot: String, item: String) -> Result<(), RunErr> {
+        if let Some(existing) = self.equipped.get(&slot) {
+            return Err(RunErr::SlotTaken(slot, existing.to_owned()));
+        }
+
+        self.equipped.insert(slot, item);
+        Ok(())
+    }
+
+    /// Empties the slot, returning the item which was in it.
+    pub fn unequip(&mut self, slot: &str) -> Result<String, RunErr> {
+        self.equipped
+            .remove(slot)
+            .ok_or_else(|| RunErr::EmptySlot(slot.to_string()))
+    }
+
+    /// Skills can't be redefined either, so the rank someone has always fits the skill.
+    pub fn defineSkill(
+        &mut self,
+        skill: String,
+        max: u32,
+        prerequisites: Vec<Expression>,
+    ) -> Result<(), RunErr> {
+        if self.skills.contains_key(&skill) {
+            return Err(RunErr::TriedToRedefineSkill(skill));
+        }
+
+        self.skills.insert(skill, (max, prerequisites));
+        Ok(())
+    }
+
+    /// Returns the maximum rank of the skill and its prerequisites.
+    pub fn getSkill(&self, skill: &str) -> Result<(u32, &[Expression]), RunErr> {
+        self.skills
+            .get(skill)
+            .map(|(max, prerequisites)| (*max, prerequisites.as_slice()))
+            .ok_or_else(|| RunErr::NonexistentSkill(skill.to_string()))
+    }
+
+    /// The rank the entity has in the skill, which is zero if it hasn't learned it.
+    pub fn getRank(&self, entity: &str, skill: &str) -> u32 {
+        self.getSkills(entity)
+            .iter()
+            .find(|(learned, _)| learned == skill)
+            .map_or(0, |(_, rank)| *rank)
+    }
+
+    pub fn setRank(&mut self, entity: &str, skill: &str, rank: u32) {
+        let learned = self.ranks.entry(entity.to_string()).or_default();
+        match learned.iter_mut().find(|(existing, _)| existing == skill) {
+            Some(existing) => existing.1 = rank,
+            None => learned.push((skill.to_string(), rank)),
+        }
+    }
+
+    /// The skills the entity has learned, with their ranks.
+    pub fn getSkills(&self, entity: &str) -> &[(String, u32)] {
+        self.ranks.get(entity).map_or(&[], |learned| learned)
+    }
+
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn applySetting(&mut self, setting: Setting) {
+        // The settings change how numbers are computed, so every derived variable has to be computed again.
+        self.cache.get_mut().clear();
+        match setting {
+            Setting::Rounding(rounding) => self.settings.rounding = rounding,
+            Setting::Numbers(mode) => self.settings.numbers = mode,
+            Setting::Precision(precision) => self.settings.precision = precision,
+            Setting::Iterations(iterations) => self.settings.iterations = iterations,
+            Setting::Depth(depth) => self.settings.depth = depth,
+            Setting::Refill(refill) => self.settings.refill = refill,
+        }
+    }
 }
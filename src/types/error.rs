@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use super::engine::MAX_DERIVED_DEPTH;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
     IO(String),
@@ -11,11 +13,23 @@ pub enum Error {
 pub enum ParsingErr {
     UnrecognizedExpression(String),
     ExpressionParsing,
+    ExpressionTooDeep,
     StringOverflow,
     InvalidNumberOfArguments,
     CommandLeftOpen,
     UnrecognizedCommand(String),
-    NumberParsing(std::num::ParseIntError),
+    UnrecognizedSetting(String),
+    UnrecognizedFormat(String),
+    /// An `if`, `repeat` or `while` without an `end`.
+    BlockLeftOpen,
+    /// Blocks nested inside of each other deeper than the limit.
+    BlockTooDeep,
+    /// An `end` which doesn't close any block, an `elseif` or `else` outside of an `if`, or after its `else`.
+    UnexpectedBlockMarker(String),
+    /// A string without its closing quote.
+    StringLeftOpen,
+    DuplicateField(String),
+    DuplicateParameter(String),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -24,6 +38,65 @@ pub enum RunErr {
     TriedToInitializeExistingVariable,
     TriedToModifyNonexistentVariable,
     TriedToGetNonexistentVariable,
+    NegativeExponent,
+    FractionalExponent,
+    TriedToModifyDerivedVariable(String),
+    CyclicDependency(String),
+    /// A derived variable whose expression rolls dice, so it would come out different on every read.
+    RandomDerived(String),
+    /// Reading the variable went through more derived variables inside of each other than the limit.
+    DerivedTooDeep(String),
+    DivisionByZero(String),
+    /// The first value is the variable (or, if there isn't one, the expression) and the second is the command.
+    ArithmeticOverflow(String, String),
+    /// A block marker which wasn't folded into a block by the parser.
+    UnexpectedBlockMarker(String),
+    /// A loop would have run more times than the limit, which is the value.
+    IterationLimit(u32),
+    /// The expression giving the number of repetitions isn't a whole number.
+    InvalidRepeatCount(String),
+    UnknownFunction(String),
+    /// The function, and the number of arguments it takes.
+    WrongArity(String, String),
+    /// The expression, the type it has, and the type it should have had.
+    TypeMismatch(String, String, String),
+    NonexistentEntity(String),
+    NonexistentClass(String),
+    TriedToRedefineClass(String),
+    MissingItem(String, String),
+    InvalidQuantity(String),
+    UnknownMacro(String),
+    RecursionLimit(u32),
+    NotAVariable(String),
+    /// An argument standing for the name of an item, a skill or the like, which is neither a name nor a string.
+    NotAName(String),
+    InvalidRange(String),
+    /// A `clamp` whose lower bound is above the upper one.
+    InvalidBounds(String),
+    InvalidSeed(String),
+    InvalidMaximum(String),
+    InvalidDuration(String),
+    InvalidLevel(String),
+    /// The experience of a curve whose thresholds stopped growing, and the limit of levels it went over at once.
+    EndlessCurve(String, u32),
+    NonexistentModifier(String),
+    /// The source, and what it belongs to, which takes its modifiers away on its own.
+    ModifierNotRevocable(String, String),
+    NonexistentItem(String),
+    TriedToRedefineItem(String),
+    SlotTaken(String, String),
+    EmptySlot(String),
+    NotEnoughPoints(String, String, String),
+    /// Points were allocated from a variable which wasn't created with `points`.
+    NotPoints(String),
+    NonexistentSkill(String),
+    TriedToRedefineSkill(String),
+    SkillAlreadyLearned(String, String),
+    SkillNotLearned(String, String),
+    MaxRankReached(String, u32),
+    PrerequisiteNotMet(String, String, String),
+    /// The skill, and a prerequisite of it which names neither a skill nor a variable.
+    UnknownPrerequisite(String, String),
 }
 
 impl From<RunErr> for Error {
@@ -44,12 +117,6 @@ impl From<std::io::Error> for Error {
     }
 }
 
-impl From<std::num::ParseIntError> for ParsingErr {
-    fn from(err: std::num::ParseIntError) -> ParsingErr {
-        ParsingErr::NumberParsing(err)
-    }
-}
-
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -70,6 +137,8 @@ impl Display for ParsingErr {
                     format!("Unrecognized expression: {expr}"),
                 ParsingErr::ExpressionParsing =>
                     String::from("There was an error with expression parsing!"),
+                ParsingErr::ExpressionTooDeep =>
+                    String::from("An expression was nested too deeply to be parsed"),
                 ParsingErr::StringOverflow =>
                     String::from("Somehow, the contents of the file caused a string overflow."),
                 ParsingErr::InvalidNumberOfArguments =>
@@ -78,8 +147,20 @@ impl Display for ParsingErr {
                     String::from("The command at the end of the file was left open"),
                 ParsingErr::UnrecognizedCommand(command) =>
                     format!("Unrecognized command: {command}"),
-                ParsingErr::NumberParsing(parse_int_error) =>
-                    format!("Failed conversion to i32: '{parse_int_error}'"),
+                ParsingErr::UnrecognizedSetting(setting) =>
+                    format!("Unrecognized setting: {setting}"),
+                ParsingErr::UnrecognizedFormat(format) => format!("Unrecognized format: {format}"),
+                ParsingErr::BlockLeftOpen =>
+                    String::from("A block was left open, it needs an 'end'"),
+                ParsingErr::BlockTooDeep =>
+                    String::from("Blocks were nested inside of each other too deeply"),
+                ParsingErr::UnexpectedBlockMarker(marker) =>
+                    format!("The '{marker}' doesn't belong to any open block"),
+                ParsingErr::StringLeftOpen => String::from("A string is missing its closing quote"),
+                ParsingErr::DuplicateField(field) =>
+                    format!("The field '{field}' is given more than once"),
+                ParsingErr::DuplicateParameter(parameter) =>
+                    format!("The parameter '{parameter}' is given more than once"),
             }
         )
     }
@@ -91,12 +172,108 @@ impl Display for RunErr {
             f,
             "{}",
             match self {
-                RunErr::MissingInput => "Input the name of the file as the first argument",
+                RunErr::MissingInput =>
+                    String::from("Input the name of the file as the first argument"),
                 RunErr::TriedToInitializeExistingVariable =>
-                    "Tried to initialize already existing variable",
+                    String::from("Tried to initialize already existing variable"),
                 RunErr::TriedToModifyNonexistentVariable =>
-                    "Tried to modify a nonexistent variable",
-                RunErr::TriedToGetNonexistentVariable => "Tried to get a nonexistent variable",
+                    String::from("Tried to modify a nonexistent variable"),
+                RunErr::TriedToGetNonexistentVariable =>
+                    String::from("Tried to get a nonexistent variable"),
+                RunErr::NegativeExponent =>
+                    String::from("Tried to raise a number to a negative power"),
+                RunErr::FractionalExponent =>
+                    String::from("Tried to raise a number to a fractional power"),
+                RunErr::TriedToModifyDerivedVariable(variable) =>
+                    format!("Tried to modify '{variable}', which is derived from other variables"),
+                RunErr::CyclicDependency(variable) =>
+                    format!("The derived variable '{variable}' depends on itself"),
+                RunErr::RandomDerived(variable) => format!(
+                    "The derived variable '{variable}' can't roll, since it's computed again on every read"
+                ),
+                RunErr::DerivedTooDeep(variable) => format!(
+                    "Reading '{variable}' went through more than {MAX_DERIVED_DEPTH} derived variables inside of each other"
+                ),
+                RunErr::DivisionByZero(expression) =>
+                    format!("Tried to divide by zero in {expression}"),
+                RunErr::ArithmeticOverflow(subject, command) =>
+                    format!("The value of '{subject}' overflowed in the command '{command}'"),
+                RunErr::UnexpectedBlockMarker(marker) =>
+                    format!("The '{marker}' can't be run outside of a block"),
+                RunErr::IterationLimit(limit) => format!(
+                    "A loop tried to run more than {limit} times, the limit can be raised with 'config iterations'"
+                ),
+                RunErr::InvalidRepeatCount(expression) =>
+                    format!("Can't repeat something {expression} times, it has to be a whole number"),
+                RunErr::UnknownFunction(name) => format!("There is no function called '{name}'"),
+                RunErr::WrongArity(name, arity) => format!("The function '{name}' takes {arity}"),
+                RunErr::TypeMismatch(expression, found, expected) =>
+                    format!("'{expression}' is {found}, but it has to be {expected}"),
+                RunErr::NonexistentEntity(entity) => format!("There is no entity called '{entity}'"),
+                RunErr::NonexistentClass(class) => format!("There is no class called '{class}'"),
+                RunErr::MissingItem(list, item) => format!("'{list}' doesn't have {item}"),
+                RunErr::InvalidQuantity(expression) =>
+                    format!("The quantity '{expression}' isn't a positive whole number"),
+                RunErr::TriedToRedefineClass(class) =>
+                    format!("The class '{class}' is already defined"),
+                RunErr::InvalidRange(expression) => format!(
+                    "The bounds of '{expression}' have to be whole numbers, with the lower one first"
+                ),
+                RunErr::InvalidBounds(expression) => format!(
+                    "The bounds of '{expression}' are the wrong way around, the lower one has to come first"
+                ),
+                RunErr::InvalidSeed(seed) =>
+                    format!("The seed '{seed}' has to be a positive whole number"),
+                RunErr::InvalidMaximum(pool) =>
+                    format!("The maximum of the pool '{pool}' has to be above zero"),
+                RunErr::InvalidDuration(duration) =>
+                    format!("The duration '{duration}' isn't a whole number of ticks or chapters"),
+                RunErr::InvalidLevel(level) =>
+                    format!("The level '{level}' has to be a whole number of at least one"),
+                RunErr::EndlessCurve(experience, limit) => format!(
+                    "'{experience}' gained more than {limit} levels at once, the thresholds of its curve have to keep growing"
+                ),
+                RunErr::NonexistentModifier(source) =>
+                    format!("There are no modifiers from '{source}'"),
+                RunErr::ModifierNotRevocable(source, owner) => format!(
+                    "The modifiers from '{source}' belong to {owner}, so they can't be revoked"
+                ),
+                RunErr::NonexistentItem(item) => format!("There is no item called '{item}'"),
+                RunErr::TriedToRedefineItem(item) =>
+                    format!("The item '{item}' is already defined"),
+                RunErr::SlotTaken(slot, item) =>
+                    format!("The slot '{slot}' already holds '{item}'"),
+                RunErr::EmptySlot(slot) => format!("There is nothing in the slot '{slot}'"),
+                RunErr::NotEnoughPoints(pool, wanted, available) => format!(
+                    "Tried to spend {wanted} points from '{pool}', which only has {available}"
+                ),
+                RunErr::NotPoints(pool) => format!(
+                    "'{pool}' doesn't hold unspent points, they have to be created with 'points'"
+                ),
+                RunErr::NonexistentSkill(skill) => format!("There is no skill called '{skill}'"),
+                RunErr::TriedToRedefineSkill(skill) =>
+                    format!("The skill '{skill}' is already defined"),
+                RunErr::SkillAlreadyLearned(entity, skill) =>
+                    format!("'{entity}' already knows '{skill}', so it can only be ranked up"),
+                RunErr::SkillNotLearned(entity, skill) =>
+                    format!("'{entity}' has to learn '{skill}' before ranking it up"),
+                RunErr::MaxRankReached(skill, max) =>
+                    format!("'{skill}' is already at its maximum rank of {max}"),
+                RunErr::PrerequisiteNotMet(entity, skill, prerequisite) => format!(
+                    "'{entity}' doesn't meet the prerequisite '{prerequisite}' of '{skill}'"
+                ),
+                RunErr::UnknownPrerequisite(skill, prerequisite) => format!(
+                    "The prerequisite '{prerequisite}' of '{skill}' isn't a skill, nor a variable"
+                ),
+                RunErr::UnknownMacro(name) => format!("There is no macro called '{name}'"),
+                RunErr::RecursionLimit(limit) =>
+                    format!("Macros went over the limit of {limit} calls inside of each other"),
+                RunErr::NotAName(argument) => format!(
+                    "The argument '{argument}' is used as a name in the macro, so it has to be a name or a string"
+                ),
+                RunErr::NotAVariable(argument) => format!(
+                    "The argument '{argument}' is used as a variable in the macro, so it has to be one"
+                ),
             }
         )
     }
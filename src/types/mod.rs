@@ -1,14 +1,84 @@
 use std::fmt::Display;
 
+use curve::Curve;
+use effect::TimeUnit;
+use engine::{NumberMode, Refill, Rounding};
+use number::Number;
+
+pub mod bigint;
+pub mod curve;
+pub mod decimal;
+pub mod effect;
 pub mod engine;
 pub mod error;
+pub mod modifier;
+pub mod number;
+pub mod random;
+pub mod value;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expression {
-    Value(i32),
+    Value(Number),
+    /// A string literal, written in quotes, like `"Spellblade"`.
+    Str(String),
     Variable(String),
     Binary(BiOperation, Box<Expression>, Box<Expression>),
     Unary(UnOperation, Box<Expression>),
+    /// A call of a built-in function, like `clamp(hp, 0, max_hp)`.
+    Call(String, Vec<Expression>),
+    /// Dice, like `2d6`, with how many of them there are and how many sides they have.
+    /// They're rolled every time the expression is evaluated.
+    Dice(u32, u32),
+}
+
+impl Expression {
+    /// Returns the names of all the variables used in the expression.
+    pub fn variables(&self) -> Vec<String> {
+        match self {
+            Expression::Value(_) | Expression::Str(_) | Expression::Dice(_, _) => vec![],
+            Expression::Variable(var) => vec![var.to_owned()],
+            Expression::Binary(_, lhand, rhand) => {
+                let mut variables = lhand.variables();
+                variables.append(&mut rhand.variables());
+                variables
+            }
+            Expression::Unary(_, expr) => expr.variables(),
+            Expression::Call(_, arguments) => arguments
+                .iter()
+                .flat_map(|argument| argument.variables())
+                .collect(),
+        }
+    }
+
+    /// Returns the same expression, but with every variable replaced by what the given function returns for it,
+    /// which can be another variable or a whole expression. The first error the function returns stops it.
+    pub fn replaceVariables<E>(
+        &self,
+        replace: &impl Fn(&str) -> Result<Expression, E>,
+    ) -> Result<Expression, E> {
+        Ok(match self {
+            Expression::Value(_) | Expression::Str(_) | Expression::Dice(_, _) => self.clone(),
+            Expression::Variable(var) => replace(var)?,
+            Expression::Binary(op, lhand, rhand) => Expression::Binary(
+                *op,
+                Box::new(lhand.replaceVariables(replace)?),
+                Box::new(rhand.replaceVariables(replace)?),
+            ),
+            Expression::Unary(op, expr) => {
+                Expression::Unary(*op, Box::new(expr.replaceVariables(replace)?))
+            }
+            Expression::Call(name, arguments) => Expression::Call(
+                name.to_owned(),
+                arguments
+                    .iter()
+                    .map(|argument| argument.replaceVariables(replace))
+                    .collect::<Result<Vec<Expression>, E>>()?,
+            ),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,27 +86,294 @@ pub enum BiOperation {
     Add,
     Subtract,
     Multiply,
+    Divide,
+    Modulo,
     Exponentiate,
+    /// Comparisons and logical operators result in `1` for true and `0` for false.
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnOperation {
     Minus,
+    Not,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Content {
     Text(String),
     Command(Command),
+    /// An `if` block, with the condition and contents of every branch (the `if` and any `elseif`s),
+    /// followed by the contents of the `else`, which are empty if there isn't one.
+    ///
+    /// Only the contents of the first branch whose condition holds are run, or the `else` if none of them do.
+    Conditional(Vec<(Expression, Vec<Content>)>, Vec<Content>),
+    /// The contents run as many times as the expression says, with the index (counting from 1) stored in the
+    /// variable, if one is given.
+    Repeat(Expression, Option<String>, Vec<Content>),
+    /// The contents run for as long as the condition holds, with the index like in `Repeat`.
+    While(Expression, Option<String>, Vec<Content>),
+    /// A macro, with the names of its parameters and its contents. Running it only defines the macro,
+    /// the contents are run whenever it's called.
+    Macro(String, Vec<String>, Vec<Content>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
-    Let(String, i32),
-    Set(String, i32),
-    Add(String, i32),
-    Subtract(String, i32),
-    Write(String),
+    Let(String, Expression),
+    Set(String, Expression),
+    Add(String, Expression),
+    Subtract(String, Expression),
+    Derive(String, Expression),
+    Config(Setting),
+    Write(Expression, Format),
+    Entity(String),
+    /// Writes the fields of the entity, with their values.
+    Fields(String),
+    Delete(String),
+    /// A template for entities, with the formula of every field.
+    Class(String, Vec<(String, Expression)>),
+    /// Creates an entity from a class, with some of its fields overridden.
+    New(String, String, Vec<(String, Expression)>),
+    /// Creates an empty list.
+    List(String),
+    /// Adds an item to a list, this many times.
+    Push(String, Expression, Expression),
+    /// Takes an item out of a list, this many times.
+    Remove(String, Expression, Expression),
+    /// Runs a macro with the given arguments.
+    Call(String, Vec<Expression>),
+    /// Creates a full pool with the given maximum.
+    Pool(String, Expression),
+    /// Starts an effect with the given name, on the target, which lasts for the given time.
+    Effect(String, String, Expression, Expression, TimeUnit),
+    /// Counts the effects down by this many ticks.
+    Tick(Expression),
+    /// Marks the end of a chapter, counting down the effects which last for chapters.
+    Chapter,
+    /// Writes the active effects, either all of them or the ones on a variable or an entity.
+    Effects(Option<String>),
+    /// Gives the experience a curve, which levels up whenever it's gained.
+    Curve(String, Curve),
+    /// Adds a modifier to the stat, from the source, which is a percentage if the flag is set.
+    Modifier(String, String, Expression, bool),
+    /// Removes the modifiers from the source, from every stat.
+    Revoke(String),
+    /// Writes how the value of the stat is made up.
+    Explain(String),
+    /// An item which goes into the slot, with the bonuses it gives to the stats of whoever wears it.
+    Item(String, String, Vec<(String, Expression)>),
+    /// Puts the item on the entity, adding its bonuses.
+    Equip(String, String),
+    /// Takes the item out of the slot of the entity, removing its bonuses.
+    Unequip(String, String),
+    /// Unspent points, like `hero.points`, which can't go below zero, starting with the value.
+    Points(String, Expression),
+    /// Moves this many unspent points from the pool (the second name) into the stat.
+    Allocate(String, String, Expression),
+    /// A skill, with its maximum rank and prerequisites.
+    Skill(String, Expression, Vec<Expression>),
+    /// Gives the entity the first rank of the skill.
+    Learn(String, String),
+    /// Raises the rank the entity has in the skill.
+    Rankup(String, String),
+    /// Writes the skills the entity has learned, with their ranks.
+    Skills(String),
+    /// Starts the rolls over from the seed.
+    Seed(u64),
+    /// Writes the value of the expression like `write`, and records it among the rolls.
+    Roll(Expression),
+    /// The block markers only show up while parsing, as they're folded into a `Content::Conditional`
+    /// together with everything between them.
+    If(Expression),
+    ElseIf(Expression),
+    Else,
+    Repeat(Expression, Option<String>),
+    While(Expression, Option<String>),
+    Def(String, Vec<String>),
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Rounding(Rounding),
+    Numbers(NumberMode),
+    Precision(u32),
+    Iterations(u32),
+    Depth(u32),
+    Refill(Refill),
+}
+
+/// How the value given to the `write` command is written into the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// As is, so whole numbers without a decimal point and decimals with as many decimal places as they need.
+    Plain,
+    /// With exactly this many decimal places.
+    Decimals(u32),
+    /// A list, with every item on its own line, starting with a dash.
+    Bullets,
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // Negative numbers are bracketed, so that `(-2) ^ 2` isn't mistaken for `-(2 ^ 2)`.
+            Self::Value(v) if v.isNegative() => write!(f, "({v})"),
+            Self::Value(v) => write!(f, "{v}"),
+            // Written back with the quotes, escaping the characters which the parser expects to be escaped.
+            Self::Str(text) => write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            Self::Variable(var) => write!(f, "{var}"),
+            Self::Binary(op, lhand, rhand) => write!(f, "({lhand} {op} {rhand})"),
+            Self::Unary(op, expr) => write!(f, "({op}{expr})"),
+            Self::Call(name, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{name}({})", arguments.join(", "))
+            }
+            Self::Dice(count, sides) => write!(f, "{count}d{sides}"),
+        }
+    }
+}
+
+impl Display for BiOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Add => "+",
+                Self::Subtract => "-",
+                Self::Multiply => "*",
+                Self::Divide => "/",
+                Self::Modulo => "%",
+                Self::Exponentiate => "^",
+                Self::Less => "<",
+                Self::LessOrEqual => "<=",
+                Self::Greater => ">",
+                Self::GreaterOrEqual => ">=",
+                Self::Equal => "==",
+                Self::NotEqual => "!=",
+                Self::And => "&&",
+                Self::Or => "||",
+            }
+        )
+    }
+}
+
+impl Display for UnOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Minus => write!(f, "-"),
+            Self::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl Command {
+    /// The word used to write the command in a file.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Let(_, _) => "let",
+            Self::Set(_, _) => "set",
+            Self::Add(_, _) => "add",
+            Self::Subtract(_, _) => "subtract",
+            Self::Derive(_, _) => "derive",
+            Self::Config(_) => "config",
+            Self::Write(_, _) => "write",
+            Self::Entity(_) => "entity",
+            Self::Fields(_) => "fields",
+            Self::Delete(_) => "delete",
+            Self::Class(_, _) => "class",
+            Self::New(_, _, _) => "new",
+            Self::List(_) => "list",
+            Self::Push(_, _, _) => "push",
+            Self::Remove(_, _, _) => "remove",
+            Self::Call(_, _) => "call",
+            Self::Pool(_, _) => "pool",
+            Self::Effect(_, _, _, _, _) => "effect",
+            Self::Tick(_) => "tick",
+            Self::Chapter => "chapter",
+            Self::Effects(_) => "effects",
+            Self::Curve(_, _) => "curve",
+            Self::Modifier(_, _, _, _) => "modifier",
+            Self::Revoke(_) => "revoke",
+            Self::Explain(_) => "explain",
+            Self::Item(_, _, _) => "item",
+            Self::Equip(_, _) => "equip",
+            Self::Unequip(_, _) => "unequip",
+            Self::Points(_, _) => "points",
+            Self::Allocate(_, _, _) => "allocate",
+            Self::Skill(_, _, _) => "skill",
+            Self::Learn(_, _) => "learn",
+            Self::Rankup(_, _) => "rankup",
+            Self::Skills(_) => "skills",
+            Self::Seed(_) => "seed",
+            Self::Roll(_) => "roll",
+            Self::If(_) => "if",
+            Self::ElseIf(_) => "elseif",
+            Self::Else => "else",
+            Self::Repeat(_, _) => "repeat",
+            Self::While(_, _) => "while",
+            Self::Def(_, _) => "def",
+            Self::End => "end",
+        }
+    }
+
+    /// The variable which the command creates or modifies, if there is one.
+    pub fn variable(&self) -> Option<&str> {
+        match self {
+            Self::Let(var, _)
+            | Self::Set(var, _)
+            | Self::Add(var, _)
+            | Self::Subtract(var, _)
+            | Self::Derive(var, _)
+            | Self::Entity(var)
+            | Self::Delete(var)
+            | Self::New(var, _, _)
+            | Self::List(var)
+            | Self::Push(var, _, _)
+            | Self::Remove(var, _, _)
+            | Self::Pool(var, _)
+            | Self::Effect(_, var, _, _, _)
+            | Self::Curve(var, _)
+            | Self::Modifier(var, _, _, _)
+            | Self::Equip(var, _)
+            | Self::Unequip(var, _)
+            | Self::Points(var, _)
+            | Self::Allocate(var, _, _)
+            | Self::Learn(var, _)
+            | Self::Rankup(var, _) => Some(var),
+            Self::Config(_)
+            | Self::Write(_, _)
+            | Self::Fields(_)
+            | Self::Class(_, _)
+            | Self::If(_)
+            | Self::ElseIf(_)
+            | Self::Else
+            | Self::Repeat(_, _)
+            | Self::While(_, _)
+            | Self::Call(_, _)
+            | Self::Seed(_)
+            | Self::Roll(_)
+            | Self::Tick(_)
+            | Self::Chapter
+            | Self::Effects(_)
+            | Self::Revoke(_)
+            | Self::Item(_, _, _)
+            | Self::Skill(_, _, _)
+            | Self::Skills(_)
+            | Self::Explain(_)
+            | Self::Def(_, _)
+            | Self::End => None,
+        }
+    }
 }
 
 impl Display for Content {
@@ -44,10 +381,53 @@ impl Display for Content {
         match self {
             Self::Text(t) => write!(f, "{t}"),
             Self::Command(c) => write!(f, "{c}"),
+            Self::Conditional(branches, otherwise) => {
+                for (condition, contents) in branches {
+                    write!(f, "[If {condition}] ")?;
+                    for content in contents {
+                        write!(f, "{content}")?;
+                    }
+                }
+                write!(f, "[Else] ")?;
+                for content in otherwise {
+                    write!(f, "{content}")?;
+                }
+                write!(f, "[End]")
+            }
+            Self::Repeat(times, _, contents) => {
+                write!(f, "[Repeat {times} times] ")?;
+                for content in contents {
+                    write!(f, "{content}")?;
+                }
+                write!(f, "[End]")
+            }
+            Self::While(condition, _, contents) => {
+                write!(f, "[While {condition}] ")?;
+                for content in contents {
+                    write!(f, "{content}")?;
+                }
+                write!(f, "[End]")
+            }
+            Self::Macro(name, parameters, contents) => {
+                write!(f, "[Macro {name}({})] ", parameters.join(", "))?;
+                for content in contents {
+                    write!(f, "{content}")?;
+                }
+                write!(f, "[End]")
+            }
         }
     }
 }
 
+/// Writes the fields like they're written in a file, `{ str: 10, vit: (8 + level) }`.
+fn describeFields(fields: &[(String, Expression)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(field, formula)| format!("{field}: {formula}"))
+        .collect();
+    format!("{{ {} }}", fields.join(", "))
+}
+
 impl Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -58,7 +438,79 @@ impl Display for Command {
                 Self::Set(var, v) => format!("Set {var} to be {v}"),
                 Self::Add(var, v) => format!("Add {v} to {var}"),
                 Self::Subtract(var, v) => format!("Subtract {v} from {var}"),
-                Self::Write(var) => format!("Write the variable {var}"),
+                Self::Derive(var, expr) => format!("Derive {var} from {expr}"),
+                Self::Config(setting) => format!("Change the setting {setting:?}"),
+                Self::Write(expr, Format::Plain) => format!("Write the value of {expr}"),
+                Self::Write(expr, Format::Decimals(decimals)) =>
+                    format!("Write the value of {expr} with {decimals} decimals"),
+                Self::Write(expr, Format::Bullets) =>
+                    format!("Write the items of {expr} as bullets"),
+                Self::Entity(entity) => format!("Create the entity {entity}"),
+                Self::Fields(entity) => format!("Write the fields of {entity}"),
+                Self::Delete(entity) =>
+                    format!("Delete the entity {entity} with all of its fields"),
+                Self::Class(class, fields) =>
+                    format!("Define the class {class} with {}", describeFields(fields)),
+                Self::New(entity, class, overrides) => format!(
+                    "Create the entity {entity} from the class {class} with {}",
+                    describeFields(overrides)
+                ),
+                Self::List(list) => format!("Create the empty list {list}"),
+                Self::Push(list, item, quantity) => format!("Push {item} x{quantity} into {list}"),
+                Self::Remove(list, item, quantity) =>
+                    format!("Remove {item} x{quantity} from {list}"),
+                Self::Call(name, arguments) => format!(
+                    "Call {}",
+                    Expression::Call(name.to_owned(), arguments.to_vec())
+                ),
+                Self::Pool(pool, max) => format!("Create the pool {pool} holding up to {max}"),
+                Self::Effect(name, target, amount, duration, unit) => format!(
+                    "Start the effect {name} changing {target} by {amount} for {duration} {unit:?}"
+                ),
+                Self::Tick(ticks) => format!("Count the effects down by {ticks} ticks"),
+                Self::Chapter => String::from("End the chapter"),
+                Self::Effects(None) => String::from("Write all of the active effects"),
+                Self::Effects(Some(target)) => format!("Write the active effects on {target}"),
+                Self::Curve(experience, curve) =>
+                    format!("Level up {curve}, as {experience} is gained"),
+                Self::Modifier(stat, source, amount, false) =>
+                    format!("Modify {stat} by {amount} from {source}"),
+                Self::Modifier(stat, source, amount, true) =>
+                    format!("Modify {stat} by {amount} percent from {source}"),
+                Self::Revoke(source) => format!("Remove the modifiers from {source}"),
+                Self::Explain(stat) => format!("Write how {stat} is made up"),
+                Self::Item(item, slot, bonuses) => format!(
+                    "Define the item {item} for the slot {slot} with {}",
+                    describeFields(bonuses)
+                ),
+                Self::Equip(entity, item) => format!("Equip {entity} with {item}"),
+                Self::Unequip(entity, slot) => format!("Empty the slot {slot} of {entity}"),
+                Self::Points(pool, amount) => format!("Give {pool} {amount} unspent points"),
+                Self::Allocate(stat, pool, amount) =>
+                    format!("Allocate {amount} points from {pool} to {stat}"),
+                Self::Skill(skill, max, prerequisites) => {
+                    let prerequisites: Vec<String> = prerequisites
+                        .iter()
+                        .map(|prerequisite| prerequisite.to_string())
+                        .collect();
+                    format!(
+                        "Define the skill {skill} with up to {max} ranks, requiring [{}]",
+                        prerequisites.join(", ")
+                    )
+                }
+                Self::Learn(entity, skill) => format!("Teach {skill} to {entity}"),
+                Self::Rankup(entity, skill) => format!("Rank up {skill} of {entity}"),
+                Self::Skills(entity) => format!("Write the skills of {entity}"),
+                Self::Seed(seed) => format!("Seed the rolls with {seed}"),
+                Self::Roll(expr) => format!("Roll {expr}"),
+                Self::If(condition) => format!("Start a block shown if {condition}"),
+                Self::ElseIf(condition) => format!("Otherwise, show the block if {condition}"),
+                Self::Else => String::from("Otherwise, show the block"),
+                Self::Repeat(times, _) => format!("Repeat the block {times} times"),
+                Self::While(condition, _) => format!("Repeat the block while {condition}"),
+                Self::Def(name, parameters) =>
+                    format!("Start the macro {name}({})", parameters.join(", ")),
+                Self::End => String::from("End the block"),
             }
         )
     }
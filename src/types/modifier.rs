@@ -0,0 +1,46 @@
+use std::fmt::Display;
+
+use super::number::Number;
+
+/// A part of a stat which comes from somewhere else than its base value, like the class, a piece of equipment
+/// or a buff, so that the stat can be explained.
+///
+/// The flat modifiers are added to the base first, in the order they were given in. The percentages are
+/// summed up and applied to the result after that, so `+10%` and `+10%` make `+20%`, not `+21%`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modifier {
+    pub source: String,
+    pub origin: Origin,
+    pub amount: Number,
+    pub percent: bool,
+}
+
+/// What gave a stat the modifier. Items, effects and the `modifier` command each name their sources on their own,
+/// so an item and an effect which are both called `Ring` are two different sources, and neither of them can take
+/// away what the other one gave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// The `modifier` command, like the bonus of a class or a title.
+    Command,
+    Item,
+    Effect,
+    /// The points moved into the stat by `allocate`, all of them in one modifier.
+    Allocation,
+}
+
+/// Written like it's a part of a sum, `+ 8 [Ring of Might]` or `- 10% exhaustion`. Sources with spaces in them
+/// are put in brackets, so the amounts stand out.
+impl Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.amount.isNegative() { "-" } else { "+" };
+        let amount = self.amount.to_string();
+        let percent = if self.percent { "%" } else { "" };
+
+        write!(f, "{sign} {}{percent} ", amount.trim_start_matches('-'))?;
+        if self.source.contains(char::is_whitespace) {
+            write!(f, "[{}]", self.source)
+        } else {
+            write!(f, "{}", self.source)
+        }
+    }
+}
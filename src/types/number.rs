@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use super::bigint::{BigInt, MAX_LIMBS};
+use super::decimal::Decimal;
+use super::engine::{Rounding, Settings};
+
+/// A number, as used by the variables and expressions.
+///
+/// Whole numbers are `Int` whenever they fit into an `i64`, and only become `Big` when they don't.
+/// All of the operations on them are exact, and it's up to the interpreter to reject `Big` results
+/// when the author hasn't turned on the big number mode.
+///
+/// Numbers written with a decimal point or a percent sign are `Decimal`, and any operation involving one
+/// results in a `Decimal` as well.
+///
+/// They're equal whenever they have the same value, whatever they're stored as, so `2` equals `2.0`.
+#[derive(Debug, Clone)]
+pub enum Number {
+    Int(i64),
+    Big(BigInt),
+    Decimal(Decimal),
+}
+
+impl Number {
+    /// Parses a string of decimal digits, which can be of any length, or a decimal number like `1.25`.
+    pub fn parse(digits: &str) -> Option<Number> {
+        if digits.contains('.') {
+            return Decimal::parse(digits).map(Number::Decimal);
+        }
+
+        match digits.parse::<i64>() {
+            Ok(val) => Some(Number::Int(val)),
+            Err(_) => BigInt::parse(digits)
+                .map(Number::Big)
+                .map(Number::normalized),
+        }
+    }
+
+    pub fn isBig(&self) -> bool {
+        matches!(self, Number::Big(_))
+    }
+
+    pub fn isZero(&self) -> bool {
+        match self {
+            Number::Int(val) => *val == 0,
+            Number::Big(big) => big.isZero(),
+            Number::Decimal(decimal) => decimal.isZero(),
+        }
+    }
+
+    pub fn isNegative(&self) -> bool {
+        match self {
+            Number::Int(val) => *val < 0,
+            Number::Big(big) => big.isNegative(),
+            Number::Decimal(decimal) => decimal.isNegative(),
+        }
+    }
+
+    /// Only used for whole numbers, decimals are always handled separately.
+    fn toBig(&self) -> BigInt {
+        match self {
+            Number::Int(val) => BigInt::from(*val),
+            Number::Big(big) => big.clone(),
+            Number::Decimal(decimal) => BigInt::from(decimal.toWhole().unwrap_or_default()),
+        }
+    }
+
+    /// Turns a `Big` back into an `Int` if it fits.
+    fn normalized(self) -> Number {
+        match self {
+            Number::Big(big) => big.toI64().map_or(Number::Big(big), Number::Int),
+            int => int,
+        }
+    }
+
+    fn toDecimal(&self) -> Option<Decimal> {
+        match self {
+            Number::Int(val) => Some(Decimal::from(*val)),
+            Number::Big(big) => Decimal::fromBig(big),
+            Number::Decimal(decimal) => Some(*decimal),
+        }
+    }
+
+    /// If either of the numbers is a decimal, returns both of them as decimals.
+    fn asDecimals(&self, other: &Number) -> Option<Option<(Decimal, Decimal)>> {
+        if matches!(self, Number::Decimal(_)) || matches!(other, Number::Decimal(_)) {
+            Some(self.toDecimal().zip(other.toDecimal()))
+        } else {
+            None
+        }
+    }
+
+    /// Tries the operation on `i64`s first, and only falls back to the big integers if it overflows.
+    /// If either number is a decimal, the operation is done on decimals instead.
+    ///
+    /// Returns `None` if the result is too big even for a `BigInt`, or doesn't fit into a `Decimal`.
+    fn combine(
+        &self,
+        other: &Number,
+        small: fn(i64, i64) -> Option<i64>,
+        big: fn(&BigInt, &BigInt) -> BigInt,
+        decimal: impl FnOnce(&Decimal, &Decimal) -> Option<Decimal>,
+    ) -> Option<Number> {
+        if let Some(decimals) = self.asDecimals(other) {
+            let (a, b) = decimals?;
+            return decimal(&a, &b).map(Number::Decimal);
+        }
+
+        if let (Number::Int(a), Number::Int(b)) = (self, other) {
+            if let Some(result) = small(*a, *b) {
+                return Some(Number::Int(result));
+            }
+        }
+
+        let result = big(&self.toBig(), &other.toBig());
+        if result.limbCount() > MAX_LIMBS {
+            None
+        } else {
+            Some(Number::Big(result).normalized())
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Option<Number> {
+        self.combine(other, i64::checked_add, BigInt::add, Decimal::add)
+    }
+
+    pub fn sub(&self, other: &Number) -> Option<Number> {
+        self.combine(other, i64::checked_sub, BigInt::sub, Decimal::sub)
+    }
+
+    pub fn mul(&self, other: &Number, settings: &Settings) -> Option<Number> {
+        self.combine(other, i64::checked_mul, BigInt::mul, |a, b| {
+            a.mul(b, settings.precision, settings.rounding)
+        })
+    }
+
+    pub fn neg(&self) -> Option<Number> {
+        match self {
+            Number::Int(val) => Some(
+                val.checked_neg()
+                    .map_or_else(|| Number::Big(BigInt::from(*val).neg()), Number::Int),
+            ),
+            Number::Big(big) => Some(Number::Big(big.neg()).normalized()),
+            Number::Decimal(decimal) => decimal.neg().map(Number::Decimal),
+        }
+    }
+
+    /// Turns `15` into `0.15`, for numbers written as `15%`.
+    pub fn percent(&self) -> Option<Number> {
+        self.toDecimal()
+            .map(|decimal| Number::Decimal(decimal.percent()))
+    }
+
+    /// Returns the number as an `i64`, if it's a whole number which fits into one.
+    pub fn toWhole(&self) -> Option<i64> {
+        match self {
+            Number::Int(val) => Some(*val),
+            Number::Big(big) => big.toI64(),
+            Number::Decimal(decimal) => decimal.toWhole(),
+        }
+    }
+
+    /// Any number other than zero counts as true in a condition.
+    pub fn isTrue(&self) -> bool {
+        !self.isZero()
+    }
+
+    /// Whether the number is a decimal with something after the decimal point.
+    pub fn isFractional(&self) -> bool {
+        match self {
+            Number::Decimal(decimal) => decimal.toWhole().is_none(),
+            _ => false,
+        }
+    }
+
+    /// Raises the number to a power, which mustn't be negative or fractional.
+    pub fn pow(&self, exponent: &Number, settings: &Settings) -> Option<Number> {
+        // A decimal exponent is fine as long as it's a whole number.
+        let exponent = match exponent {
+            Number::Decimal(decimal) => Number::Int(decimal.toWhole()?),
+            other => other.clone(),
+        };
+
+        // With these bases, the result doesn't grow no matter how big the exponent is. Zero to the power of zero
+        // is one, like any other base.
+        match self {
+            Number::Int(0) if exponent.isZero() => return Some(Number::Int(1)),
+            Number::Int(0 | 1) => return Some(self.clone()),
+            Number::Int(-1) => return Some(Number::Int(if exponent.isOdd() { -1 } else { 1 })),
+            _ => {}
+        }
+
+        // Any other base raised to a power this big couldn't be stored anyway.
+        let exponent = match exponent {
+            Number::Int(val) => u32::try_from(val).ok()?,
+            _ => return None,
+        };
+
+        match self {
+            Number::Int(base) if base.checked_pow(exponent).is_some() => {
+                base.checked_pow(exponent).map(Number::Int)
+            }
+            Number::Decimal(decimal) => decimal
+                .pow(exponent, settings.precision, settings.rounding)
+                .map(Number::Decimal),
+            _ => self
+                .toBig()
+                .pow(exponent)
+                .map(|big| Number::Big(big).normalized()),
+        }
+    }
+
+    fn isOdd(&self) -> bool {
+        match self {
+            Number::Int(val) => val % 2 != 0,
+            Number::Big(big) => big
+                .divRem(&BigInt::from(2))
+                .is_some_and(|(_, remainder)| !remainder.isZero()),
+            Number::Decimal(decimal) => decimal.toWhole().is_some_and(|val| val % 2 != 0),
+        }
+    }
+
+    /// Division, with the result rounded the way the author chose. If either of the numbers is a decimal,
+    /// the result is a decimal with the chosen precision, otherwise it's a whole number.
+    /// Returns `None` when dividing by zero.
+    pub fn div(&self, other: &Number, settings: &Settings) -> Option<Number> {
+        if let Some(decimals) = self.asDecimals(other) {
+            let (a, b) = decimals?;
+            return a
+                .div(&b, settings.precision, settings.rounding)
+                .map(Number::Decimal);
+        }
+
+        let (quotient, remainder) = self.toBig().divRem(&other.toBig())?;
+        let adjustment = settings.rounding.adjustment(
+            !remainder.isZero(),
+            self.isNegative() != other.isNegative(),
+            remainder.abs().mul(&BigInt::from(2)) >= other.toBig().abs(),
+        );
+
+        Some(Number::Big(quotient.add(&BigInt::from(i64::from(adjustment)))).normalized())
+    }
+
+    /// The remainder of the division, following the same rounding, so that `a == b * (a / b) + a % b` always holds.
+    /// For decimals, the quotient is rounded to a whole number, so `5.5 % 2` is `1.5`.
+    pub fn rem(&self, other: &Number, settings: &Settings) -> Option<Number> {
+        let whole = Settings {
+            precision: 0,
+            ..*settings
+        };
+        let quotient = self.div(other, &whole)?;
+        self.sub(&other.mul(&quotient, settings)?)
+    }
+
+    /// Writes the number with exactly the given amount of decimal places.
+    pub fn format(&self, decimals: u32, rounding: Rounding) -> Option<String> {
+        self.toDecimal()?.format(decimals, rounding)
+    }
+}
+
+impl From<i64> for Number {
+    fn from(val: i64) -> Self {
+        Number::Int(val)
+    }
+}
+
+/// Conditions are numbers too, with `1` meaning true and `0` false.
+impl From<bool> for Number {
+    fn from(val: bool) -> Self {
+        Number::Int(val as i64)
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(b),
+            _ => match self.asDecimals(other) {
+                Some(Some((a, b))) => a.cmp(&b),
+                // A whole number too big for a decimal is bigger than any decimal, or smaller if it's negative.
+                Some(None) if matches!(self, Number::Decimal(_)) => {
+                    if other.isNegative() {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Less
+                    }
+                }
+                Some(None) if self.isNegative() => Ordering::Less,
+                Some(None) => Ordering::Greater,
+                None => self.toBig().cmp(&other.toBig()),
+            },
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(val) => write!(f, "{val}"),
+            Number::Big(big) => write!(f, "{big}"),
+            Number::Decimal(decimal) => write!(f, "{decimal}"),
+        }
+    }
+}
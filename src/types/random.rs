@@ -0,0 +1,129 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+
+use super::number::Number;
+
+/// The most dice a single roll can have, so that a typo like `2000000d6` doesn't hang the program.
+pub const MAX_DICE: u32 = 1000;
+
+/// A roll, as it's written into the audit log, like `2d6: 3, 5`, or `(2d6 + 3): 3, 5 = 11` for a `roll` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roll {
+    /// What was rolled: the dice, the call of `random` or the expression of a `roll` command.
+    pub what: String,
+    pub results: Vec<Number>,
+    /// What the expression of a `roll` command came out to, with everything rolled inside of it as the results.
+    pub total: Option<Number>,
+}
+
+impl Display for Roll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let results: Vec<String> = self
+            .results
+            .iter()
+            .map(|result| result.to_string())
+            .collect();
+        write!(f, "{}: {}", self.what, results.join(", "))?;
+        match &self.total {
+            Some(total) => write!(f, " = {total}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A pseudo-random number generator (SplitMix64), which gives the same numbers every time it's given
+/// the same seed. That way rendering a chapter again gives the same rolls, so the story doesn't change
+/// under the author's hands.
+///
+/// Rolling happens while evaluating expressions, which only get to read the storage, so the state lives in
+/// a `Cell`. Every roll is remembered, so that the author can check them afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rng {
+    state: Cell<u64>,
+    rolls: RefCell<Vec<Roll>>,
+}
+
+#[allow(unused)]
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            state: Cell::new(seed),
+            rolls: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Starts the sequence over from the seed. The rolls made so far stay in the log.
+    pub fn reseed(&self, seed: u64) {
+        self.state.set(seed);
+    }
+
+    fn next(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+
+        let mut mixed = state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        mixed ^ (mixed >> 31)
+    }
+
+    /// A number between the bounds, including both of them. The lower bound can't be above the upper one.
+    ///
+    /// Numbers which would make some results more likely than others are thrown away, instead of simply
+    /// taking the remainder.
+    pub fn between(&self, low: i64, high: i64) -> i64 {
+        let range = (i128::from(high) - i128::from(low) + 1) as u128;
+        let zone = (u128::from(u64::MAX) + 1) / range * range;
+
+        loop {
+            let drawn = u128::from(self.next());
+            if drawn < zone {
+                return (i128::from(low) + (drawn % range) as i128) as i64;
+            }
+        }
+    }
+
+    /// Rolls the dice, returning every one of them, and writes them into the log.
+    pub fn roll(&self, count: u32, sides: u32) -> Vec<i64> {
+        let results: Vec<i64> = (0..count)
+            .map(|_| self.between(1, i64::from(sides)))
+            .collect();
+
+        self.record(
+            format!("{count}d{sides}"),
+            results.iter().map(|result| Number::from(*result)).collect(),
+        );
+        results
+    }
+
+    pub fn record(&self, what: String, results: Vec<Number>) {
+        self.rolls.borrow_mut().push(Roll {
+            what,
+            results,
+            total: None,
+        });
+    }
+
+    /// How many rolls are in the log, so that the ones made from now on can be told apart.
+    pub fn logged(&self) -> usize {
+        self.rolls.borrow().len()
+    }
+
+    /// Replaces every roll made since the log had `from` of them with a single one, holding all of their results
+    /// and the total they came out to. A `roll` command is one entry in the log, however many dice it has.
+    pub fn combine(&self, from: usize, what: String, total: Number) {
+        let mut rolls = self.rolls.borrow_mut();
+        let from = from.min(rolls.len());
+        let results = rolls.drain(from..).flat_map(|roll| roll.results).collect();
+        rolls.push(Roll {
+            what,
+            results,
+            total: Some(total),
+        });
+    }
+
+    /// Every roll made so far, in the order they were made in.
+    pub fn rolls(&self) -> Vec<Roll> {
+        self.rolls.borrow().clone()
+    }
+}
@@ -0,0 +1,328 @@
+use super::bigint::BigInt;
+use super::decimal::Decimal;
+use super::engine::{Rounding, Settings};
+use super::number::Number;
+use super::random::Rng;
+
+fn big(digits: &str) -> BigInt {
+    match digits.strip_prefix('-') {
+        Some(rest) => BigInt::parse(rest).expect("Invalid number").neg(),
+        None => BigInt::parse(digits).expect("Invalid number"),
+    }
+}
+
+#[test]
+fn bigIntegers() {
+    assert_eq!(big("000").to_string(), "0");
+    assert_eq!(big("-0"), BigInt::from(0));
+    assert_eq!(big("1000000000").to_string(), "1000000000");
+    assert_eq!(
+        big("123456789012345678901234567890").to_string(),
+        "123456789012345678901234567890"
+    );
+    assert_eq!(BigInt::parse("12a"), None);
+    assert_eq!(BigInt::parse(""), None);
+
+    assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+    assert_eq!(BigInt::from(i64::MIN).toI64(), Some(i64::MIN));
+    assert_eq!(BigInt::from(i64::MAX).toI64(), Some(i64::MAX));
+    assert_eq!(big("9223372036854775808").toI64(), None);
+    assert_eq!(big("-9223372036854775809").toI64(), None);
+
+    let a = big("99999999999999999999");
+    let b = big("-1");
+    assert_eq!(a.sub(&b).to_string(), "100000000000000000000");
+    assert_eq!(b.sub(&a).to_string(), "-100000000000000000000");
+    assert_eq!(a.add(&a.neg()), BigInt::from(0));
+    assert_eq!(
+        a.mul(&a).to_string(),
+        "9999999999999999999800000000000000000001"
+    );
+    assert_eq!(
+        BigInt::from(2).pow(100).map(|n| n.to_string()),
+        Some("1267650600228229401496703205376".to_string())
+    );
+    assert_eq!(BigInt::from(10).pow(1_000_000), None);
+
+    let (quotient, remainder) = big("-1267650600228229401496703205377")
+        .divRem(&big("4294967296"))
+        .expect("Division by zero");
+    assert_eq!(quotient.to_string(), "-295147905179352825856");
+    assert_eq!(remainder.to_string(), "-1");
+    assert_eq!(a.divRem(&BigInt::from(0)), None);
+
+    assert!(big("-5") < big("3"));
+    assert!(big("-50000000000000000000") < big("-3"));
+    assert!(big("50000000000000000000") > big("49999999999999999999"));
+}
+
+#[test]
+fn numbers() {
+    let settings = Settings::default();
+    let max = Number::Int(i64::MAX);
+    let one = Number::from(1);
+
+    // Overflowing `i64` turns the number into a big one, and going back turns it back.
+    let over = max.add(&one).expect("Too big");
+    assert!(over.isBig());
+    assert_eq!(over.to_string(), "9223372036854775808");
+    assert_eq!(over.sub(&one), Some(max.clone()));
+    assert_eq!(
+        Number::Int(i64::MIN).neg().map(|n| n.to_string()),
+        Some("9223372036854775808".to_string())
+    );
+
+    assert_eq!(
+        Number::parse("123456789123456789123456789"),
+        Some(Number::Big(big("123456789123456789123456789")))
+    );
+    assert_eq!(Number::parse("42"), Some(Number::Int(42)));
+
+    // Powers with bases that don't grow work with any exponent
+    let huge = Number::parse("100000000000000000000000").expect("");
+    assert_eq!(Number::from(1).pow(&huge, &settings), Some(Number::from(1)));
+    assert_eq!(
+        Number::from(-1).pow(&huge, &settings),
+        Some(Number::from(1))
+    );
+    assert_eq!(Number::from(0).pow(&huge, &settings), Some(Number::from(0)));
+    assert_eq!(
+        Number::from(0).pow(&Number::from(0), &settings),
+        Some(Number::from(1))
+    );
+    assert_eq!(Number::from(2).pow(&huge, &settings), None);
+    assert_eq!(
+        Number::from(3)
+            .pow(&Number::from(50), &settings)
+            .map(|n| n.to_string()),
+        Some("717897987691852588770249".to_string())
+    );
+
+    // Rounding works the same for big numbers as for small ones
+    let divided = |lhand: &Number, rounding: Rounding| {
+        lhand
+            .div(
+                &Number::from(-2),
+                &Settings {
+                    rounding,
+                    ..settings
+                },
+            )
+            .map(|n| n.to_string())
+    };
+    let odd = Number::parse("100000000000000000001").expect("");
+    assert_eq!(
+        divided(&odd, Rounding::Floor),
+        Some("-50000000000000000001".to_string())
+    );
+    assert_eq!(
+        divided(&odd, Rounding::Truncate),
+        Some("-50000000000000000000".to_string())
+    );
+    assert_eq!(
+        divided(&odd, Rounding::Round),
+        Some("-50000000000000000001".to_string())
+    );
+    assert_eq!(
+        odd.rem(&Number::from(-2), &settings),
+        Some(Number::from(-1))
+    );
+    assert_eq!(
+        Number::Int(i64::MIN).div(&Number::from(-1), &settings),
+        Number::Int(i64::MIN).neg()
+    );
+
+    // Whole numbers and decimals share how a truncated quotient is moved, which only happens with a remainder
+    assert_eq!(Rounding::Floor.adjustment(true, true, false), -1);
+    assert_eq!(Rounding::Floor.adjustment(true, false, true), 0);
+    assert_eq!(Rounding::Truncate.adjustment(true, true, true), 0);
+    assert_eq!(Rounding::Round.adjustment(true, false, true), 1);
+    assert_eq!(Rounding::Round.adjustment(true, true, false), 0);
+    assert_eq!(Rounding::Round.adjustment(false, true, true), 0);
+}
+
+fn decimal(text: &str) -> Decimal {
+    match text.strip_prefix('-') {
+        Some(rest) => Decimal::parse(rest)
+            .and_then(|d| d.neg())
+            .expect("Invalid decimal"),
+        None => Decimal::parse(text).expect("Invalid decimal"),
+    }
+}
+
+#[test]
+fn decimals() {
+    assert_eq!(decimal("1.50"), decimal("1.5"));
+    assert_eq!(decimal("1.50").to_string(), "1.5");
+    assert_eq!(decimal("0.05").to_string(), "0.05");
+    assert_eq!(decimal("-0.05").to_string(), "-0.05");
+    assert_eq!(decimal("15").percent().to_string(), "0.15");
+    assert_eq!(Decimal::parse("1.2.3"), None);
+    assert_eq!(Decimal::parse(".5"), None);
+    assert_eq!(Decimal::parse("0.0000000000000000001"), None);
+
+    // No floating point drift
+    assert_eq!(decimal("0.1").add(&decimal("0.2")), Some(decimal("0.3")));
+
+    assert_eq!(
+        decimal("1.25").mul(&decimal("1.25"), 4, Rounding::Floor),
+        Some(decimal("1.5625"))
+    );
+    assert_eq!(
+        decimal("1.25").mul(&decimal("1.25"), 2, Rounding::Floor),
+        Some(decimal("1.56"))
+    );
+    assert_eq!(
+        decimal("1.25").mul(&decimal("1.25"), 2, Rounding::Round),
+        Some(decimal("1.56"))
+    );
+    assert_eq!(
+        decimal("-1.25").mul(&decimal("1.25"), 2, Rounding::Floor),
+        Some(decimal("-1.57"))
+    );
+    assert_eq!(
+        decimal("-1.25").mul(&decimal("1.25"), 2, Rounding::Truncate),
+        Some(decimal("-1.56"))
+    );
+
+    assert_eq!(
+        decimal("1").div(&decimal("3"), 4, Rounding::Floor),
+        Some(decimal("0.3333"))
+    );
+    assert_eq!(
+        decimal("2").div(&decimal("3"), 4, Rounding::Round),
+        Some(decimal("0.6667"))
+    );
+    assert_eq!(decimal("2").div(&decimal("0"), 4, Rounding::Round), None);
+
+    assert_eq!(
+        decimal("1.1").pow(3, 4, Rounding::Floor),
+        Some(decimal("1.331"))
+    );
+
+    assert_eq!(
+        decimal("1.5").format(2, Rounding::Floor),
+        Some("1.50".to_string())
+    );
+    assert_eq!(
+        decimal("2.345").format(2, Rounding::Round),
+        Some("2.35".to_string())
+    );
+    assert_eq!(
+        decimal("2.345").format(0, Rounding::Floor),
+        Some("2".to_string())
+    );
+
+    assert!(decimal("0.15") < decimal("0.2"));
+    assert!(decimal("-1.5") < decimal("-1.25"));
+}
+
+#[test]
+fn mixedNumbers() {
+    let settings = Settings::default();
+
+    // Any operation with a decimal results in a decimal
+    let sum = Number::from(2).add(&Number::Decimal(decimal("0.5")));
+    assert_eq!(sum, Some(Number::Decimal(decimal("2.5"))));
+    assert!(matches!(
+        Number::from(200).mul(&Number::Decimal(decimal("0.15")), &settings),
+        Some(Number::Decimal(_))
+    ));
+
+    // Numbers are equal when their values are, whatever they're stored as, so `==` agrees with the ordering
+    assert_eq!(Number::from(2), Number::Decimal(decimal("2.0")));
+    assert_ne!(Number::from(2), Number::Decimal(decimal("2.5")));
+    assert_eq!(Number::Big(big("42")), Number::from(42));
+    assert_eq!(
+        Number::from(200).mul(&Number::Decimal(decimal("0.15")), &settings),
+        Some(Number::Decimal(decimal("30")))
+    );
+    assert_eq!(
+        Number::from(5).div(&Number::from(2), &settings),
+        Some(Number::from(2))
+    );
+    assert_eq!(
+        Number::Decimal(decimal("5")).div(&Number::from(2), &settings),
+        Some(Number::Decimal(decimal("2.5")))
+    );
+    assert_eq!(
+        Number::Decimal(decimal("5.5")).rem(&Number::from(2), &settings),
+        Some(Number::Decimal(decimal("1.5")))
+    );
+
+    assert_eq!(
+        Number::parse("1.25"),
+        Some(Number::Decimal(decimal("1.25")))
+    );
+    assert_eq!(
+        Number::from(15).percent(),
+        Some(Number::Decimal(decimal("0.15")))
+    );
+    assert!(Number::Decimal(decimal("0.5")) < Number::from(1));
+    assert!(
+        Number::parse("100000000000000000000000000000000000000000").expect("")
+            > Number::Decimal(decimal("1.5"))
+    );
+    assert!(Number::Decimal(decimal("2"))
+        .pow(&Number::Decimal(decimal("0.5")), &settings)
+        .is_none());
+}
+
+#[test]
+fn randomNumbers() {
+    // The same seed gives the same numbers, and a different one doesn't.
+    let draw = |rng: &Rng| (0..20).map(|_| rng.between(1, 6)).collect::<Vec<i64>>();
+    let first = draw(&Rng::new(1234));
+    assert_eq!(first, draw(&Rng::new(1234)));
+    assert_ne!(first, draw(&Rng::new(4321)));
+
+    let rng = Rng::new(7);
+    rng.reseed(1234);
+    assert_eq!(first, draw(&rng));
+
+    // Every side comes up, and nothing outside of them does.
+    let mut seen = [0; 6];
+    for _ in 0..600 {
+        let side = rng.between(1, 6);
+        assert!((1..=6).contains(&side), "{side} isn't a side of a die");
+        seen[side as usize - 1] += 1;
+    }
+    assert!(
+        seen.iter().all(|count| *count > 50),
+        "{seen:?} is too uneven"
+    );
+
+    // The whole range of numbers works too.
+    rng.between(i64::MIN, i64::MAX);
+    assert_eq!(rng.between(-3, -3), -3);
+
+    // Dice are written into the log, together with anything else that's recorded.
+    let rng = Rng::new(1);
+    let dice = rng.roll(3, 1);
+    assert_eq!(dice, vec![1, 1, 1]);
+    rng.record(String::from("the loot"), vec![Number::from(5)]);
+    assert_eq!(
+        rng.rolls()
+            .iter()
+            .map(|roll| roll.to_string())
+            .collect::<Vec<String>>(),
+        vec!["3d1: 1, 1, 1", "the loot: 5"]
+    );
+
+    // Everything rolled since a point in the log can be folded into a single entry with its total.
+    let start = rng.logged();
+    rng.roll(2, 1);
+    rng.roll(1, 1);
+    rng.combine(start, String::from("(2d1 + 1d1 + 3)"), Number::from(6));
+    assert_eq!(
+        rng.rolls()
+            .iter()
+            .map(|roll| roll.to_string())
+            .collect::<Vec<String>>(),
+        vec![
+            "3d1: 1, 1, 1",
+            "the loot: 5",
+            "(2d1 + 1d1 + 3): 1, 1, 1 = 6"
+        ]
+    );
+}
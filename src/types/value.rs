@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use super::engine::{Refill, Settings};
+use super::number::Number;
+
+/// What a variable holds. Most of them are numbers, but names, classes and titles are strings,
+/// an inventory is a list, and health or mana are pools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(Number),
+    Str(String),
+    List(List),
+    Pool(Pool),
+}
+
+/// A resource which goes up and down between zero and its maximum, like health or mana.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pool {
+    current: Number,
+    max: Number,
+}
+
+impl Pool {
+    /// A full pool. The maximum is expected to be above zero.
+    pub fn new(max: Number) -> Pool {
+        Pool {
+            current: max.clone(),
+            max,
+        }
+    }
+
+    pub fn current(&self) -> &Number {
+        &self.current
+    }
+
+    pub fn max(&self) -> &Number {
+        &self.max
+    }
+
+    /// The same pool with a different current value, which is kept between zero and the maximum.
+    pub fn withCurrent(&self, current: Number) -> Pool {
+        Pool {
+            current: current.min(self.max.clone()).max(Number::from(0)),
+            max: self.max.clone(),
+        }
+    }
+
+    /// The same pool with a different maximum, which is expected to be above zero. How full the pool is
+    /// afterwards depends on the refill setting. Returns `None` if the new current value overflows.
+    pub fn withMax(&self, max: Number, settings: &Settings) -> Option<Pool> {
+        let current = match settings.refill {
+            Refill::Keep => self.current.clone(),
+            Refill::Proportional => self.current.mul(&max, settings)?.div(&self.max, settings)?,
+        };
+
+        Some(
+            Pool {
+                current: self.current.clone(),
+                max,
+            }
+            .withCurrent(current),
+        )
+    }
+
+    /// How full the pool is, from 0 to 100. It's divided like any other number, so a pool of whole numbers
+    /// gives a whole percentage, rounded according to the settings.
+    pub fn percent(&self, settings: &Settings) -> Option<Number> {
+        self.current
+            .mul(&Number::from(100), settings)?
+            .div(&self.max, settings)
+    }
+}
+
+/// A collection of items, each with how many of it there are, like `Health Potion x3`.
+/// The items keep the order they were first added in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct List {
+    items: Vec<(String, i64)>,
+}
+
+impl List {
+    pub fn new() -> List {
+        Default::default()
+    }
+
+    /// Adds the items, returning `None` if there would be too many of them to count.
+    /// The quantities are expected to be positive.
+    pub fn push(&mut self, item: &str, quantity: i64) -> Option<()> {
+        match self.items.iter_mut().find(|(name, _)| name == item) {
+            Some((_, held)) => *held = held.checked_add(quantity)?,
+            None => self.items.push((item.to_string(), quantity)),
+        }
+        Some(())
+    }
+
+    /// Takes the items out, returning `None` if there aren't enough of them. Nothing is removed in that case.
+    /// An item is forgotten once there are none of it left.
+    pub fn remove(&mut self, item: &str, quantity: i64) -> Option<()> {
+        let index = self.items.iter().position(|(name, _)| name == item)?;
+
+        let held = &mut self.items[index].1;
+        if *held < quantity {
+            return None;
+        }
+        *held -= quantity;
+        if *held == 0 {
+            self.items.remove(index);
+        }
+        Some(())
+    }
+
+    /// How many of the item there are, or how many items there are in total if none is given.
+    pub fn count(&self, item: Option<&str>) -> i64 {
+        self.items
+            .iter()
+            .filter(|(name, _)| item.is_none_or(|item| name == item))
+            .fold(0, |total, (_, quantity)| total.saturating_add(*quantity))
+    }
+
+    /// The items as they're written, with the quantity after any item there's more than one of.
+    pub fn entries(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|(name, quantity)| describeItem(name, *quantity))
+            .collect()
+    }
+}
+
+/// `Iron Sword` for one of them, `Health Potion x3` for more.
+pub fn describeItem(item: &str, quantity: i64) -> String {
+    match quantity {
+        1 => item.to_string(),
+        quantity => format!("{item} x{quantity}"),
+    }
+}
+
+impl Value {
+    /// The name of the type, for error messages.
+    pub fn typeName(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::List(_) => "a list",
+            Value::Pool(_) => "a pool",
+        }
+    }
+}
+
+impl Value {
+    /// A pool stands for its current value in calculations, anything else is left as it is.
+    pub fn numeric(self) -> Value {
+        match self {
+            Value::Pool(pool) => Value::Number(pool.current),
+            other => other,
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(val: i64) -> Self {
+        Value::Number(Number::from(val))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Str(text.to_string())
+    }
+}
+
+/// Strings are written as they are, without the quotes, lists as their items separated by commas,
+/// and pools as how full they are, like `73/120`.
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{number}"),
+            Value::Str(text) => write!(f, "{text}"),
+            Value::List(list) => write!(f, "{}", list.entries().join(", ")),
+            Value::Pool(pool) => write!(f, "{}/{}", pool.current, pool.max),
+        }
+    }
+}